@@ -0,0 +1,319 @@
+//! Sprite-sheet animations defined in `.anim.ron` files.
+//!
+//! An [`AnimationSet`] describes how a sprite sheet is cut into frames and the named clips played
+//! from them. Any entity with a `SpriteSheetBundle` can be animated by giving it an [`Animator`]
+//! that points to a set. Sets are hot-reloaded, and entities keep playing the same clip by name.
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadedAsset},
+    math::vec2,
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::Rect,
+};
+use serde::Deserialize;
+
+use crate::AppState;
+
+/// How the sprite sheet is cut into frames. Frames are numbered in the order they are listed, or
+/// left to right and then top to bottom for grids.
+#[derive(Deserialize, Clone, Debug)]
+pub enum AtlasLayout {
+    /// Frames of the same size, in pixels.
+    Grid {
+        frame_size: (f32, f32),
+        columns: usize,
+        rows: usize,
+        /// Position of the first frame.
+        #[serde(default)]
+        offset: (f32, f32),
+        /// Gap between two frames.
+        #[serde(default)]
+        spacing: (f32, f32),
+    },
+    /// Frames anywhere in the image, as `(x, y, width, height)` in pixels.
+    Regions(Vec<(f32, f32, f32, f32)>),
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopMode {
+    /// Starts over from the first frame after the last one.
+    Loop,
+    /// Stays on the last frame once finished.
+    Once,
+    /// Plays forwards and backwards in turn.
+    PingPong,
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::Loop
+    }
+}
+
+/// An event sent when a frame of a clip starts being displayed, such as a footstep.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FrameEvent {
+    /// Position of the frame in the clip, starting at 0.
+    pub frame: usize,
+    pub name: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnimationClip {
+    /// Indices of the frames in the atlas, in playing order.
+    pub frames: Vec<usize>,
+    pub frame_duration_ms: u64,
+    #[serde(default)]
+    pub mode: LoopMode,
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "3f0c7a52-8d1e-4b7a-a6f3-2c94e1d5b870"]
+pub struct AnimationSet {
+    /// Path to the sprite sheet, relative to the assets folder.
+    pub image: String,
+    /// Size of the sprite sheet in pixels.
+    pub image_size: (f32, f32),
+    pub layout: AtlasLayout,
+    /// Size to draw frames at, in bevy units.
+    pub sprite_size: (f32, f32),
+    pub clips: HashMap<String, AnimationClip>,
+    /// Atlas built from `layout`, created by the loader.
+    #[serde(skip)]
+    pub atlas: Handle<TextureAtlas>,
+}
+
+impl AnimationSet {
+    fn texture_atlas(&self, texture: Handle<Image>) -> TextureAtlas {
+        let image_size = vec2(self.image_size.0, self.image_size.1);
+        let mut atlas = TextureAtlas::new_empty(texture, image_size);
+
+        let regions = match &self.layout {
+            AtlasLayout::Grid {
+                frame_size,
+                columns,
+                rows,
+                offset,
+                spacing,
+            } => (0..*rows)
+                .flat_map(|row| (0..*columns).map(move |column| (row, column)))
+                .map(|(row, column)| {
+                    (
+                        offset.0 + column as f32 * (frame_size.0 + spacing.0),
+                        offset.1 + row as f32 * (frame_size.1 + spacing.1),
+                        frame_size.0,
+                        frame_size.1,
+                    )
+                })
+                .collect(),
+            AtlasLayout::Regions(regions) => regions.clone(),
+        };
+        for (x, y, width, height) in regions {
+            // The last column or row of a grid may be cut off by the edge of the image
+            atlas.add_texture(Rect {
+                min: vec2(x, y),
+                max: vec2(x + width, y + height).min(image_size),
+            });
+        }
+
+        atlas
+    }
+}
+
+pub struct AnimationSetLoader;
+
+impl AssetLoader for AnimationSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut set = ron::de::from_bytes::<AnimationSet>(bytes)?;
+
+            let image_path: AssetPath = Path::new(&set.image).to_owned().into();
+            let atlas = set.texture_atlas(load_context.get_handle(image_path.clone()));
+            set.atlas = load_context.set_labeled_asset(
+                "atlas",
+                LoadedAsset::new(atlas).with_dependency(image_path.clone()),
+            );
+            load_context.set_default_asset(LoadedAsset::new(set).with_dependency(image_path));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+/// Sent when an animated entity reaches a frame with a [`FrameEvent`].
+#[derive(Clone, Debug)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// Plays clips of an [`AnimationSet`] on the `TextureAtlasSprite` of its entity. The atlas and
+/// sprite size are set from the animation set once it is loaded.
+#[derive(Component, Default)]
+pub struct Animator {
+    pub set: Handle<AnimationSet>,
+    clip: String,
+    /// Position of the current frame in the clip.
+    frame: usize,
+    timer: Timer,
+    /// Whether the current frame was just entered and its events haven't been sent yet.
+    frame_started: bool,
+    /// Whether a ping-pong clip is playing backwards.
+    reversed: bool,
+    finished: bool,
+    pub flip_x: bool,
+}
+
+impl Animator {
+    pub fn new(set: Handle<AnimationSet>, clip: &str) -> Self {
+        let mut animator = Self { set, ..default() };
+        animator.restart(clip);
+        animator
+    }
+
+    /// Plays the given clip from its first frame, unless it is already playing.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.restart(clip);
+        }
+    }
+
+    /// Plays the given clip from its first frame.
+    pub fn restart(&mut self, clip: &str) {
+        self.clip = clip.to_owned();
+        self.frame = 0;
+        self.timer = Timer::new(Duration::ZERO, true);
+        self.frame_started = true;
+        self.reversed = false;
+        self.finished = false;
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    /// Whether a clip that doesn't loop has reached the end of its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Moves to the next frame of the clip. Returns whether the frame changed.
+    fn advance(&mut self, clip: &AnimationClip) -> bool {
+        let last = clip.frames.len() - 1;
+        if self.finished {
+            return false;
+        }
+        match clip.mode {
+            LoopMode::Loop => self.frame = if self.frame < last { self.frame + 1 } else { 0 },
+            LoopMode::Once if self.frame < last => self.frame += 1,
+            LoopMode::Once => {
+                self.finished = true;
+                return false;
+            }
+            LoopMode::PingPong if last == 0 => {}
+            LoopMode::PingPong => {
+                if self.frame == 0 {
+                    self.reversed = false;
+                } else if self.frame == last {
+                    self.reversed = true;
+                }
+                if self.reversed {
+                    self.frame -= 1;
+                } else {
+                    self.frame += 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AnimationSet>()
+            .add_asset_loader(AnimationSetLoader)
+            .add_event::<AnimationEvent>()
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(animate_sprites));
+    }
+}
+
+pub fn animate_sprites(
+    time: Res<Time>,
+    sets: Res<Assets<AnimationSet>>,
+    mut events: EventWriter<AnimationEvent>,
+    mut query: Query<(
+        Entity,
+        &mut Animator,
+        &mut TextureAtlasSprite,
+        &mut Handle<TextureAtlas>,
+    )>,
+) {
+    for (entity, mut animator, mut sprite, mut atlas) in query.iter_mut() {
+        let set = if let Some(set) = sets.get(&animator.set) {
+            set
+        } else {
+            continue;
+        };
+        if *atlas != set.atlas {
+            *atlas = set.atlas.clone();
+        }
+        let size = Some(vec2(set.sprite_size.0, set.sprite_size.1));
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+
+        let clip = match set.clips.get(&animator.clip) {
+            Some(clip) if !clip.frames.is_empty() => clip,
+            _ => continue,
+        };
+        // The clip may have gotten shorter after a reload
+        animator.frame = animator.frame.min(clip.frames.len() - 1);
+
+        let mut send_events = |frame: usize| {
+            for event in clip.events.iter().filter(|event| event.frame == frame) {
+                events.send(AnimationEvent {
+                    entity,
+                    name: event.name.clone(),
+                });
+            }
+        };
+        if animator.frame_started {
+            animator.frame_started = false;
+            send_events(animator.frame);
+        }
+
+        let duration = Duration::from_millis(clip.frame_duration_ms.max(1));
+        if animator.timer.duration() != duration {
+            animator.timer.set_duration(duration);
+        }
+        animator.timer.tick(time.delta());
+        for _ in 0..animator.timer.times_finished() {
+            if animator.advance(clip) {
+                send_events(animator.frame);
+            }
+        }
+
+        let index = clip.frames[animator.frame];
+        if sprite.index != index {
+            sprite.index = index;
+        }
+        if sprite.flip_x != animator.flip_x {
+            sprite.flip_x = animator.flip_x;
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Parallax backgrounds drawn behind the tilemap.
+//!
+//! Background sets are defined in `assets/backgrounds.bg.ron`. Each level picks one through its
+//! `Background` LDtk field, or uses the default set if it doesn't have one.
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::Deserialize;
+
+use crate::{
+    follow::CameraFollow,
+    player::CurrentRoom,
+    world::{GameWorld, TILE_SIZE},
+    Player, WorldProject,
+};
+
+/// Z coordinate of the furthest background layer. The camera doesn't render anything below -0.1,
+/// and tile layers start at 0.
+const BACKGROUND_BASE_Z: f32 = -0.09;
+const BACKGROUND_LAYER_Z_STEP: f32 = 0.01;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BackgroundLayer {
+    /// Path to the image to display, relative to the assets folder.
+    pub image: String,
+    /// How much the layer follows the camera. (0, 0) scrolls along with the level, while (1, 1)
+    /// stays fixed on screen.
+    pub scroll_factor: (f32, f32),
+    /// Position of the layer when the camera is at the world origin, in bevy units.
+    #[serde(default)]
+    pub offset: (f32, f32),
+    #[serde(default)]
+    pub repeat_x: bool,
+    #[serde(default)]
+    pub repeat_y: bool,
+}
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "5b7bcbe8-6f0a-4f0e-9a43-bd2a6c6c1e3b"]
+pub struct Backgrounds {
+    /// Background set used by levels without a `Background` field.
+    pub default: Option<String>,
+    /// Named background sets, each one drawn back to front.
+    pub sets: HashMap<String, Vec<BackgroundLayer>>,
+}
+
+pub struct BackgroundsLoader;
+
+impl AssetLoader for BackgroundsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let loaded_asset = LoadedAsset::new(ron::de::from_bytes::<Backgrounds>(bytes)?);
+            load_context.set_default_asset(loaded_asset);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bg.ron"]
+    }
+}
+
+/// Holds the background definitions to use and the set currently displayed.
+#[derive(Default)]
+pub struct BackgroundSettings {
+    pub backgrounds: Handle<Backgrounds>,
+    current_set: Option<String>,
+}
+
+/// A spawned background layer. Its children are the repeated copies of the image.
+#[derive(Component)]
+pub struct BackgroundLayerSprite {
+    image: Handle<Image>,
+    scroll_factor: Vec2,
+    offset: Vec2,
+    repeat_x: bool,
+    repeat_y: bool,
+}
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BackgroundSettings>()
+            .add_asset::<Backgrounds>()
+            .add_asset_loader(BackgroundsLoader)
+            .add_system(change_background)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                scroll_backgrounds.after(crate::follow::follow),
+            );
+    }
+}
+
+fn change_background(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut settings: ResMut<BackgroundSettings>,
+    mut background_events: EventReader<AssetEvent<Backgrounds>>,
+    backgrounds: Res<Assets<Backgrounds>>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    current_room: Query<&CurrentRoom, With<Player>>,
+    current_room_changed: Query<(), (With<Player>, Changed<CurrentRoom>)>,
+    spawned_layers: Query<Entity, With<BackgroundLayerSprite>>,
+) {
+    let backgrounds_changed = background_events.iter().any(|event| {
+        matches!(event, AssetEvent::Created { handle } | AssetEvent::Modified { handle } if handle == &settings.backgrounds)
+    });
+
+    if !backgrounds_changed && current_room_changed.is_empty() {
+        return;
+    }
+
+    let (backgrounds, map, current_room) = match (
+        backgrounds.get(&settings.backgrounds),
+        maps.get(&world.project),
+        current_room.get_single(),
+    ) {
+        (Some(backgrounds), Some(map), Ok(current_room)) => (backgrounds, map, current_room),
+        _ => return,
+    };
+
+    let set = map
+        .level(&current_room.id)
+        .and_then(|level| level.field("Background"))
+        .and_then(|value| value.as_str())
+        .map(str::to_owned)
+        .or_else(|| backgrounds.default.clone());
+
+    if !backgrounds_changed && set == settings.current_set {
+        return;
+    }
+
+    for entity in spawned_layers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let layers = match set.as_ref().and_then(|set| backgrounds.sets.get(set)) {
+        Some(layers) => layers,
+        None => {
+            if let Some(set) = &set {
+                warn!("Background set '{}' does not exist", set);
+            }
+            settings.current_set = set;
+            return;
+        }
+    };
+
+    for (i, layer) in layers.iter().enumerate() {
+        let image: Handle<Image> = asset_server.load(layer.image.as_str());
+        let z = BACKGROUND_BASE_Z + i as f32 * BACKGROUND_LAYER_Z_STEP;
+
+        commands
+            .spawn_bundle(TransformBundle::from_transform(
+                Transform::from_xyz(0., 0., z).with_scale(Vec3::new(
+                    1. / TILE_SIZE as f32,
+                    1. / TILE_SIZE as f32,
+                    1.,
+                )),
+            ))
+            .insert(BackgroundLayerSprite {
+                image: image.clone(),
+                scroll_factor: layer.scroll_factor.into(),
+                offset: layer.offset.into(),
+                repeat_x: layer.repeat_x,
+                repeat_y: layer.repeat_y,
+            })
+            .with_children(|children| {
+                let x_range = if layer.repeat_x { -1..=1 } else { 0..=0 };
+                let y_range = if layer.repeat_y { -1..=1 } else { 0..=0 };
+                for x in x_range {
+                    for y in y_range.clone() {
+                        children
+                            .spawn_bundle(SpriteBundle {
+                                texture: image.clone(),
+                                ..default()
+                            })
+                            .insert(BackgroundCopy(IVec2::new(x, y)));
+                    }
+                }
+            });
+    }
+
+    settings.current_set = set;
+}
+
+/// Position of a repeated background image relative to its layer, in image sizes.
+#[derive(Component)]
+struct BackgroundCopy(IVec2);
+
+fn scroll_backgrounds(
+    images: Res<Assets<Image>>,
+    camera: Query<&Transform, (With<Camera>, With<CameraFollow>)>,
+    mut layers: Query<(&BackgroundLayerSprite, &mut Transform), Without<Camera>>,
+    mut copies: Query<
+        (&BackgroundCopy, &Parent, &mut Transform),
+        (Without<BackgroundLayerSprite>, Without<Camera>),
+    >,
+) {
+    let camera = if let Ok(camera) = camera.get_single() {
+        camera.translation.truncate()
+    } else {
+        return;
+    };
+
+    for (layer, mut transform) in layers.iter_mut() {
+        let mut position = layer.offset + camera * layer.scroll_factor;
+
+        // Snap repeating layers to the copy closest to the camera so that the screen is always
+        // covered.
+        if let Some(image) = images.get(&layer.image) {
+            let size = image.size() / TILE_SIZE as f32;
+            if layer.repeat_x {
+                position.x += ((camera.x - position.x) / size.x).round() * size.x;
+            }
+            if layer.repeat_y {
+                position.y += ((camera.y - position.y) / size.y).round() * size.y;
+            }
+        }
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+
+    for (copy, parent, mut transform) in copies.iter_mut() {
+        if let Ok((layer, _)) = layers.get(parent.0) {
+            if let Some(image) = images.get(&layer.image) {
+                let offset = copy.0.as_vec2() * image.size();
+                transform.translation.x = offset.x;
+                transform.translation.y = offset.y;
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Renders the collision map of an LDtk or Tiled world to a PNG image and a text file.
+//!
+//! Usage: `collision-export <world> <output directory>`
+//!
+//! Writes `<world>.png` and `<world>.txt` to the output directory, named after the world file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use platformer_test::{export, WorldProject};
+
+/// Pixels per tile in the exported image.
+const IMAGE_SCALE: u32 = 4;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (world_path, output_dir) = match args.as_slice() {
+        [world_path, output_dir] => (Path::new(world_path), PathBuf::from(output_dir)),
+        _ => anyhow::bail!("Usage: collision-export <world> <output directory>"),
+    };
+
+    let project = WorldProject::load_from_file(world_path)
+        .with_context(|| format!("Could not load {}", world_path.display()))?;
+    let name = world_path
+        .file_stem()
+        .context("The world path has no file name")?;
+
+    std::fs::create_dir_all(&output_dir)?;
+
+    let image_path = output_dir.join(name).with_extension("png");
+    export::collision_image(&project, IMAGE_SCALE).save(&image_path)?;
+    println!("Wrote {}", image_path.display());
+
+    let ascii_path = output_dir.join(name).with_extension("txt");
+    std::fs::write(&ascii_path, export::collision_ascii(&project))?;
+    println!("Wrote {}", ascii_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Checks LDtk and Tiled worlds for errors without opening a window.
+//!
+//! Usage: `ldtk-check <world>...`
+//!
+//! Exits with a non-zero status code if any of the worlds fails to load or has any issues, so it can
+//! be used to gate level changes.
+
+use std::{path::Path, process::ExitCode};
+
+use platformer_test::WorldProject;
+
+fn check(path: &Path) -> usize {
+    let project = match WorldProject::load_from_file(path) {
+        Ok(project) => project,
+        Err(err) => {
+            println!("{}: could not load project: {}", path.display(), err);
+            return 1;
+        }
+    };
+
+    let errors: Vec<_> = project
+        .validate()
+        .err()
+        .unwrap_or_default()
+        .into_iter()
+        .chain(project.lint())
+        .collect();
+
+    for error in errors.iter() {
+        println!("{}: {}", path.display(), error);
+    }
+
+    errors.len()
+}
+
+fn main() -> ExitCode {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: ldtk-check <world>...");
+        return ExitCode::from(2);
+    }
+
+    let error_count: usize = paths.iter().map(|path| check(Path::new(path))).sum();
+
+    if error_count == 0 {
+        println!("No issues found");
+        ExitCode::SUCCESS
+    } else {
+        println!("Found {} issue(s)", error_count);
+        ExitCode::FAILURE
+    }
+}
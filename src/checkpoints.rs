@@ -0,0 +1,274 @@
+//! Checkpoints spawned from `Checkpoint` LDtk entities, and respawning the player when they die.
+//!
+//! Touching a checkpoint makes it the place the player respawns at, or the start point until one
+//! is touched. The player dies when touching the `Acid` layer or falling out of the world, and in
+//! debug mode when pressing K.
+
+use std::time::Duration;
+
+use bevy::{math::vec2, prelude::*, sprite::Rect};
+
+use crate::{
+    physics::{PhysicsFrozen, RectCollision, RectExtras, SensedBodies, SensorBody, Velocity},
+    player::is_in_level,
+    transition::{PlayTransition, RoomTransitionSettings},
+    validation::START_POINT_IDENTIFIER,
+    warp::arrival_position,
+    world::{entity_rect, world_changed, GameWorld, LevelTile},
+    AppState, Player, WorldProject,
+};
+
+pub const CHECKPOINT_IDENTIFIER: &str = "Checkpoint";
+
+const RESPAWN_TRANSITION_DURATION: Duration = Duration::from_millis(600);
+/// Time after dying during which the player can't die again, so that hazards aren't triggered
+/// again before the player has been moved away from them.
+const RESPAWN_GRACE_TIME: Duration = Duration::from_millis(1000);
+const CHECKPOINT_Z: f32 = 9.;
+const CHECKPOINT_COLOR: Color = Color::rgba(0.6, 0.6, 0.6, 0.6);
+const ACTIVE_CHECKPOINT_COLOR: Color = Color::rgba(0.3, 0.9, 0.4, 0.6);
+
+#[derive(Component, Debug)]
+pub struct Checkpoint {
+    pub iid: String,
+}
+
+#[derive(Default)]
+pub struct Checkpoints {
+    /// IID of the checkpoint the player respawns at. The start point is used without one.
+    pub active: Option<String>,
+    last_death: Option<Duration>,
+}
+
+/// Kills the player, who then respawns at the active checkpoint.
+#[derive(Debug, Clone)]
+pub struct KillPlayer;
+
+/// Sensor that detects hazards touched by the player. Child of the player.
+#[derive(Component)]
+struct HazardSensor;
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Checkpoints>()
+            .add_event::<KillPlayer>()
+            .add_system(spawn_checkpoints)
+            .add_system(add_hazard_sensor)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(activate_checkpoints)
+                    .with_system(kill_player.before(respawn_player))
+                    .with_system(respawn_player),
+            );
+        #[cfg(debug_assertions)]
+        app.add_system(kill_player_debug.before(respawn_player));
+    }
+}
+
+fn spawn_checkpoints(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    maps: Res<Assets<WorldProject>>,
+    world: Res<GameWorld>,
+    mut checkpoints: ResMut<Checkpoints>,
+    spawned: Query<Entity, With<Checkpoint>>,
+) {
+    if !world_changed(&world, &mut map_events) {
+        return;
+    }
+
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // The active checkpoint may be gone after switching worlds
+    if let Some(active) = &checkpoints.active {
+        if map.entity_by_iid(active).is_none() {
+            checkpoints.active = None;
+        }
+    }
+
+    for (level, entity) in map
+        .entities()
+        .filter(|(_, entity)| entity.identifier == CHECKPOINT_IDENTIFIER)
+    {
+        let rect = entity_rect(level, entity);
+        let active = checkpoints.active.as_ref() == Some(&entity.iid);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: if active {
+                        ACTIVE_CHECKPOINT_COLOR
+                    } else {
+                        CHECKPOINT_COLOR
+                    },
+                    custom_size: Some(rect.size()),
+                    ..default()
+                },
+                transform: Transform::from_translation(
+                    ((rect.min + rect.max) / 2.).extend(CHECKPOINT_Z),
+                ),
+                ..default()
+            })
+            .insert(RectCollision::centered(rect.size()))
+            .insert(Checkpoint {
+                iid: entity.iid.clone(),
+            });
+    }
+}
+
+fn add_hazard_sensor(mut commands: Commands, players: Query<Entity, Added<Player>>) {
+    for player in players.iter() {
+        commands.entity(player).with_children(|children| {
+            children
+                .spawn_bundle((
+                    GlobalTransform::default(),
+                    Transform::default(),
+                    // A little smaller than the player, so that grazing a hazard isn't deadly
+                    RectCollision {
+                        rect: Rect::from_min_size(vec2(0.1, 0.1), vec2(0.8, 0.8)),
+                    },
+                    SensorBody {
+                        mask: LevelTile::HAZARD,
+                    },
+                ))
+                .insert(HazardSensor);
+        });
+    }
+}
+
+fn activate_checkpoints(
+    mut checkpoints: ResMut<Checkpoints>,
+    player: Query<(&GlobalTransform, &RectCollision), With<Player>>,
+    mut checkpoint_query: Query<(&GlobalTransform, &RectCollision, &Checkpoint, &mut Sprite)>,
+) {
+    let player_rect = if let Ok((transform, collision)) = player.get_single() {
+        collision.rect.translate(transform.translation.truncate())
+    } else {
+        return;
+    };
+
+    let touched = checkpoint_query
+        .iter()
+        .find(|(transform, collision, _, _)| {
+            collision
+                .rect
+                .translate(transform.translation.truncate())
+                .intersects(player_rect)
+        })
+        .map(|(_, _, checkpoint, _)| checkpoint.iid.clone());
+    if touched.is_none() || touched == checkpoints.active {
+        return;
+    }
+
+    info!("Reached checkpoint {:?}", touched);
+    checkpoints.active = touched;
+    for (_, _, checkpoint, mut sprite) in checkpoint_query.iter_mut() {
+        sprite.color = if checkpoints.active.as_ref() == Some(&checkpoint.iid) {
+            ACTIVE_CHECKPOINT_COLOR
+        } else {
+            CHECKPOINT_COLOR
+        };
+    }
+}
+
+/// Kills the player when touching hazards or falling out of the world.
+fn kill_player(
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    frozen: Res<PhysicsFrozen>,
+    player: Query<&GlobalTransform, With<Player>>,
+    hazard_sensors: Query<&SensedBodies, With<HazardSensor>>,
+    mut kill_events: EventWriter<KillPlayer>,
+) {
+    let (map, transform) = match (maps.get(&world.project), player.get_single()) {
+        (Some(map), Ok(transform)) => (map, transform),
+        _ => return,
+    };
+    // Sensors keep their last readings and the player may be between places while frozen
+    if frozen.0 {
+        return;
+    }
+
+    let position = transform.translation.truncate();
+    if hazard_sensors.iter().any(|sensed| sensed.world) {
+        kill_events.send(KillPlayer);
+    } else if !map.levels.iter().any(|level| is_in_level(level, position)) {
+        info!("Player fell out of the world");
+        kill_events.send(KillPlayer);
+    }
+}
+
+/// Kills the player when pressing K in debug mode.
+#[cfg(debug_assertions)]
+fn kill_player_debug(
+    keys: Res<Input<KeyCode>>,
+    debug: Res<crate::debug::DebugMode>,
+    mut kill_events: EventWriter<KillPlayer>,
+) {
+    if debug.active && keys.just_pressed(KeyCode::K) {
+        kill_events.send(KillPlayer);
+    }
+}
+
+fn respawn_player(
+    time: Res<Time>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    mut checkpoints: ResMut<Checkpoints>,
+    mut kill_events: EventReader<KillPlayer>,
+    mut player: Query<(&mut Player, &mut Velocity)>,
+    mut transition_events: EventWriter<PlayTransition>,
+) {
+    if kill_events.iter().count() == 0 {
+        return;
+    }
+    let now = time.time_since_startup();
+    if checkpoints
+        .last_death
+        .map_or(false, |last_death| now < last_death + RESPAWN_GRACE_TIME)
+    {
+        return;
+    }
+
+    let (map, (mut player, mut velocity)) =
+        match (maps.get(&world.project), player.get_single_mut()) {
+            (Some(map), Ok(player)) => (map, player),
+            _ => return,
+        };
+
+    let respawn_point = checkpoints
+        .active
+        .as_ref()
+        .and_then(|iid| map.entity_by_iid(iid))
+        .or_else(|| map.find_entity(START_POINT_IDENTIFIER));
+    let (level, entity) = if let Some(respawn_point) = respawn_point {
+        respawn_point
+    } else {
+        error!("Could not find a place to respawn the player at");
+        return;
+    };
+
+    info!("Player died, respawning in level {}", level.identifier);
+    checkpoints.last_death = Some(now);
+    player.reset();
+    **velocity = Vec2::ZERO;
+    // Teleporting moves the camera and updates the current room
+    transition_events.send(PlayTransition {
+        settings: RoomTransitionSettings {
+            duration: RESPAWN_TRANSITION_DURATION,
+            freeze: RESPAWN_TRANSITION_DURATION,
+            fade: true,
+        },
+        teleport_to: Some(arrival_position(level, entity)),
+        switch_world: None,
+    });
+}
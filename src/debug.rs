@@ -17,7 +17,9 @@ impl Plugin for DebugPlugin {
 
 #[cfg(debug_assertions)]
 fn debug_mode_activator(input: Res<Input<KeyCode>>, mut debug: ResMut<DebugMode>) {
-    if input.just_pressed(KeyCode::I) {
+    // Ctrl+I opens the player properties window instead
+    let ctrl = input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if input.just_pressed(KeyCode::I) && !ctrl {
         debug.active = !debug.active;
     }
 }
@@ -0,0 +1,399 @@
+//! Doors and the keys, levers and pressure plates that open them, spawned from LDtk entities.
+//!
+//! Keys, levers and pressure plates list the doors they control in a `Targets` field, an array of
+//! LDtk entity references:
+//! - `Key`: Opens its targets for good once picked up.
+//! - `Lever`: Toggles its targets when the player uses the interact action next to it.
+//! - `Pressure_Plate`: Holds its targets open while the player stands on it.
+//!
+//! Doors are closed unless their `Open` field is set. Changes to their state are kept in
+//! [`DoorStates`], so they survive world reloads.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input_mapper::{self, Input},
+    physics::{RectCollision, RectExtras, StaticBody},
+    world::{entity_rect, world_changed, GameWorld},
+    worlds::WorldRegistry,
+    AppState, Player, WorldProject,
+};
+
+pub const DOOR_IDENTIFIER: &str = "Door";
+pub const KEY_IDENTIFIER: &str = "Key";
+pub const LEVER_IDENTIFIER: &str = "Lever";
+pub const PRESSURE_PLATE_IDENTIFIER: &str = "Pressure_Plate";
+
+/// Z coordinate of doors and switches, between tile layers and the player.
+const DEVICE_Z: f32 = 9.;
+/// Fraction of a door that opens or closes per second.
+const DOOR_SPEED: f32 = 4.;
+const LEVER_ANGLE: f32 = 0.5;
+
+const DOOR_COLOR: Color = Color::rgb(0.55, 0.35, 0.17);
+const KEY_COLOR: Color = Color::rgb(0.89, 0.77, 0.29);
+const LEVER_COLOR: Color = Color::rgb(0.6, 0.6, 0.65);
+const PRESSURE_PLATE_COLOR: Color = Color::rgb(0.45, 0.45, 0.5);
+
+/// State of the doors, keys and levers the player has interacted with.
+///
+/// Doors that were never opened or closed aren't listed and keep the state set in LDtk, so saving
+/// this resource restores every door and switch without storing the whole world.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DoorStates {
+    /// Whether each door is open, by world path, level identifier and door IID. Level identifiers
+    /// are only unique within a world.
+    pub levels: HashMap<String, HashMap<String, HashMap<String, bool>>>,
+    /// IIDs of the keys picked up.
+    pub collected_keys: HashSet<String>,
+    /// IIDs of the levers currently pulled.
+    pub pulled_levers: HashSet<String>,
+}
+
+impl DoorStates {
+    /// Returns whether the door has been opened or closed, or `None` if it is in its initial state.
+    pub fn is_open(&self, world: &str, level: &str, door: &str) -> Option<bool> {
+        self.levels
+            .get(world)
+            .and_then(|levels| levels.get(level))
+            .and_then(|doors| doors.get(door))
+            .copied()
+    }
+
+    pub fn set_open(&mut self, world: &str, level: &str, door: &str, open: bool) {
+        self.levels
+            .entry(world.to_owned())
+            .or_default()
+            .entry(level.to_owned())
+            .or_default()
+            .insert(door.to_owned(), open);
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Door {
+    pub iid: String,
+    /// Path of the world the door is in.
+    pub world: String,
+    /// Identifier of the level the door is in.
+    pub level: String,
+    /// Set by keys and levers.
+    pub open: bool,
+    /// Set while a pressure plate targeting the door is pressed.
+    pub held_open: bool,
+    size: Vec2,
+    /// Goes from 0 (Closed) to 1 (Open), used to animate the door.
+    openness: f32,
+}
+
+impl Door {
+    pub fn is_open(&self) -> bool {
+        self.open || self.held_open
+    }
+}
+
+/// The visible part of a door, which slides up into the ceiling as it opens.
+#[derive(Component)]
+struct DoorSprite;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SwitchKind {
+    Key,
+    Lever,
+    PressurePlate,
+}
+
+#[derive(Component, Debug)]
+pub struct DoorSwitch {
+    pub iid: String,
+    pub kind: SwitchKind,
+    /// IIDs of the doors controlled by this switch.
+    pub targets: Vec<String>,
+    /// Whether the lever is pulled or the pressure plate is being stood on.
+    pub active: bool,
+}
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DoorStates>()
+            .add_system(spawn_doors)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(update_switches)
+                    .with_system(update_doors.after(update_switches)),
+            );
+    }
+}
+
+/// Returns the IIDs of the entities referenced by an entity reference field, or by an array of
+/// them.
+pub fn entity_refs(value: &serde_json::Value) -> Vec<String> {
+    let iid = |value: &serde_json::Value| {
+        value
+            .get("entityIid")
+            .and_then(|iid| iid.as_str())
+            .map(str::to_owned)
+    };
+
+    match value {
+        serde_json::Value::Array(values) => values.iter().filter_map(iid).collect(),
+        value => iid(value).into_iter().collect(),
+    }
+}
+
+fn spawn_doors(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    maps: Res<Assets<WorldProject>>,
+    world: Res<GameWorld>,
+    registry: Res<WorldRegistry>,
+    states: Res<DoorStates>,
+    spawned: Query<Entity, Or<(With<Door>, With<DoorSwitch>)>>,
+) {
+    if !world_changed(&world, &mut map_events) {
+        return;
+    }
+
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let world_path = registry.current.as_deref().unwrap_or_default();
+    for (level, entity) in map.entities() {
+        let rect = entity_rect(level, entity);
+        let size = rect.size();
+        let transform = Transform::from_translation(((rect.min + rect.max) / 2.).extend(DEVICE_Z));
+
+        let kind = match entity.identifier.as_str() {
+            DOOR_IDENTIFIER => {
+                let open = states
+                    .is_open(world_path, &level.identifier, &entity.iid)
+                    .or_else(|| entity.field("Open").and_then(|value| value.as_bool()))
+                    .unwrap_or(false);
+
+                let mut door = commands.spawn_bundle(TransformBundle::from_transform(transform));
+                door.insert(RectCollision::centered(size))
+                    .insert(Door {
+                        iid: entity.iid.clone(),
+                        world: world_path.to_owned(),
+                        level: level.identifier.clone(),
+                        open,
+                        held_open: false,
+                        size,
+                        openness: if open { 1. } else { 0. },
+                    })
+                    .with_children(|children| {
+                        children
+                            .spawn_bundle(SpriteBundle {
+                                sprite: Sprite {
+                                    color: DOOR_COLOR,
+                                    custom_size: Some(size),
+                                    ..default()
+                                },
+                                visibility: Visibility { is_visible: !open },
+                                ..default()
+                            })
+                            .insert(DoorSprite);
+                    });
+                if !open {
+                    door.insert(StaticBody);
+                }
+                continue;
+            }
+            KEY_IDENTIFIER if states.collected_keys.contains(&entity.iid) => continue,
+            KEY_IDENTIFIER => SwitchKind::Key,
+            LEVER_IDENTIFIER => SwitchKind::Lever,
+            PRESSURE_PLATE_IDENTIFIER => SwitchKind::PressurePlate,
+            _ => continue,
+        };
+
+        let active = kind == SwitchKind::Lever && states.pulled_levers.contains(&entity.iid);
+        let (color, sprite_size) = match kind {
+            SwitchKind::Key => (KEY_COLOR, size * 0.5),
+            SwitchKind::Lever => (LEVER_COLOR, vec2(size.x * 0.2, size.y * 0.8)),
+            SwitchKind::PressurePlate => (PRESSURE_PLATE_COLOR, vec2(size.x, size.y * 0.25)),
+        };
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(sprite_size),
+                    ..default()
+                },
+                transform,
+                ..default()
+            })
+            .insert(RectCollision::centered(size))
+            .insert(DoorSwitch {
+                iid: entity.iid.clone(),
+                kind,
+                targets: entity.field("Targets").map(entity_refs).unwrap_or_default(),
+                active,
+            });
+    }
+}
+
+fn update_switches(
+    mut commands: Commands,
+    input: Res<Input>,
+    mut states: ResMut<DoorStates>,
+    player: Query<(&GlobalTransform, &RectCollision), With<Player>>,
+    mut switches: Query<(Entity, &GlobalTransform, &RectCollision, &mut DoorSwitch)>,
+    mut doors: Query<&mut Door>,
+) {
+    let player_rect = if let Ok((transform, collision)) = player.get_single() {
+        collision.rect.translate(transform.translation.truncate())
+    } else {
+        return;
+    };
+    let interacting =
+        input.actions[input_mapper::Action::Interact] == input_mapper::ActionState::JustPressed;
+
+    let mut held_open = HashSet::new();
+
+    for (entity, transform, collision, mut switch) in switches.iter_mut() {
+        let touching = collision
+            .rect
+            .translate(transform.translation.truncate())
+            .intersects(player_rect);
+
+        let mut set_targets = |open: Option<bool>| {
+            for mut door in doors
+                .iter_mut()
+                .filter(|door| switch.targets.contains(&door.iid))
+            {
+                door.open = open.unwrap_or(!door.open);
+                states.set_open(&door.world, &door.level, &door.iid, door.open);
+            }
+        };
+
+        match switch.kind {
+            SwitchKind::Key if touching => {
+                set_targets(Some(true));
+                states.collected_keys.insert(switch.iid.clone());
+                commands.entity(entity).despawn_recursive();
+            }
+            SwitchKind::Lever if touching && interacting => {
+                set_targets(None);
+                switch.active = !switch.active;
+                if switch.active {
+                    states.pulled_levers.insert(switch.iid.clone());
+                } else {
+                    states.pulled_levers.remove(&switch.iid);
+                }
+            }
+            SwitchKind::PressurePlate => {
+                switch.active = touching;
+                if touching {
+                    held_open.extend(switch.targets.iter().cloned());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for mut door in doors.iter_mut() {
+        let held = held_open.contains(&door.iid);
+        if door.held_open != held {
+            door.held_open = held;
+        }
+    }
+}
+
+fn update_doors(
+    mut commands: Commands,
+    time: Res<Time>,
+    player: Query<(&GlobalTransform, &RectCollision), With<Player>>,
+    mut doors: Query<(
+        Entity,
+        &GlobalTransform,
+        &RectCollision,
+        &mut Door,
+        Option<&StaticBody>,
+        &Children,
+    )>,
+    mut door_sprites: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<DoorSprite>>,
+    mut switch_sprites: Query<(&DoorSwitch, &mut Sprite, &mut Transform), Without<DoorSprite>>,
+) {
+    let player_rect = player
+        .get_single()
+        .ok()
+        .map(|(transform, collision)| collision.rect.translate(transform.translation.truncate()));
+
+    for (entity, transform, collision, mut door, body, children) in doors.iter_mut() {
+        let door_rect: Rect = collision.rect.translate(transform.translation.truncate());
+        // Don't close doors on top of the player, wait for them to move out instead
+        let blocked = player_rect.map_or(false, |player_rect| player_rect.intersects(door_rect));
+
+        match (door.is_open(), body.is_some()) {
+            (true, true) => {
+                commands.entity(entity).remove::<StaticBody>();
+            }
+            (false, false) if !blocked => {
+                commands.entity(entity).insert(StaticBody);
+            }
+            _ => (),
+        }
+
+        let target = if door.is_open() || body.is_none() {
+            1.
+        } else {
+            0.
+        };
+        if door.openness == target {
+            continue;
+        }
+        let step = DOOR_SPEED * time.delta_seconds();
+        door.openness = if door.openness < target {
+            (door.openness + step).min(target)
+        } else {
+            (door.openness - step).max(target)
+        };
+
+        let visible_height = door.size.y * (1. - door.openness);
+        for &child in children.iter() {
+            if let Ok((mut sprite, mut sprite_transform, mut visibility)) =
+                door_sprites.get_mut(child)
+            {
+                sprite.custom_size = Some(vec2(door.size.x, visible_height));
+                sprite_transform.translation.y = (door.size.y - visible_height) / 2.;
+                visibility.is_visible = door.openness < 1.;
+            }
+        }
+    }
+
+    for (switch, mut sprite, mut transform) in switch_sprites.iter_mut() {
+        match switch.kind {
+            SwitchKind::Lever => {
+                let angle = if switch.active {
+                    -LEVER_ANGLE
+                } else {
+                    LEVER_ANGLE
+                };
+                transform.rotation = Quat::from_rotation_z(angle);
+            }
+            SwitchKind::PressurePlate => {
+                sprite.color = if switch.active {
+                    KEY_COLOR
+                } else {
+                    PRESSURE_PLATE_COLOR
+                };
+            }
+            SwitchKind::Key => (),
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Debug overlay to paint the layers of the current level with the mouse while the game runs.
+//!
+//! IntGrid layers (e.g. Collision and Platforms) and tile layers can be edited. Painted tiles show up
+//! right away, and edits are applied to the loaded world when the mouse button is released, which
+//! updates collisions. LDtk worlds can be saved back to their files to keep working on them in
+//! LDtk. Auto-layer tiles aren't regenerated, LDtk does so once the IntGrid layers they depend on
+//! change in the editor.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use bevy::{asset::AssetServerSettings, math::vec2, prelude::*};
+use bevy_ecs_tilemap::{MapQuery, Tile, TilePos};
+use bevy_egui::egui;
+
+use crate::{
+    debug::DebugMode, ldtk::save_levels, level::TileInstance, player::CurrentRoom,
+    world::GameWorld, worlds::WorldRegistry, AppState, Player, WorldProject,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Edit {
+    Paint,
+    Erase,
+}
+
+pub struct TileEditor {
+    pub enabled: bool,
+    /// Identifier of the layer being edited.
+    pub layer: Option<String>,
+    /// Value painted on IntGrid layers.
+    pub int_grid_value: i64,
+    /// Tile painted on tile layers.
+    pub tile_id: u16,
+    /// UIDs of the levels edited since the last save.
+    dirty_levels: HashSet<i64>,
+    /// Result of the last save.
+    status: Option<String>,
+}
+
+impl Default for TileEditor {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            layer: None,
+            int_grid_value: 1,
+            tile_id: 0,
+            dirty_levels: default(),
+            status: None,
+        }
+    }
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TileEditor>()
+            .add_system(show_editor_window)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(paint_tiles.after(show_editor_window)),
+            );
+    }
+}
+
+/// Returns the position of the mouse cursor in bevy units, if it is inside the window.
+fn cursor_world_position(
+    windows: &Windows,
+    camera_transform: &GlobalTransform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    // Goes from (0, 0) on the bottom left corner to (1, 1) on the top right one
+    let t = cursor / vec2(window.width(), window.height());
+    let offset = vec2(
+        projection.left + (projection.right - projection.left) * t.x,
+        projection.bottom + (projection.top - projection.bottom) * t.y,
+    ) * projection.scale;
+    Some(camera_transform.translation.truncate() + offset)
+}
+
+/// Cell of a layer, as the indices of its level and layer in the project and its position in the
+/// layer grid.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct LayerCell {
+    level: usize,
+    layer: usize,
+    x: i64,
+    y: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum CellValue {
+    IntGrid(i64),
+    Tile(Option<TileInstance>),
+}
+
+/// Edits made since the mouse button was pressed. They are only applied to the project once it is
+/// released, since modifying the project rebuilds every tilemap and respawns the entities of the
+/// world. Tiles are updated on their tilemap right away.
+#[derive(Default)]
+struct Stroke {
+    project: Handle<WorldProject>,
+    cells: HashMap<LayerCell, CellValue>,
+}
+
+/// Returns the cell of a layer under the given position, in world pixels, along with the value an
+/// edit gives it. Returns `None` if the cell already has that value, taking the edits of the current
+/// stroke into account.
+fn find_cell_change(
+    project: &WorldProject,
+    layer_identifier: &str,
+    (x, y): (i64, i64),
+    edit: Edit,
+    editor: &TileEditor,
+    stroke: &Stroke,
+) -> Option<(LayerCell, CellValue)> {
+    let (level_index, level) = project.levels.iter().enumerate().find(|(_, level)| {
+        (level.world_x..level.world_x + level.px_wid).contains(&x)
+            && (level.world_y..level.world_y + level.px_hei).contains(&y)
+    })?;
+    let (layer_index, layer) = level
+        .layers
+        .as_ref()?
+        .iter()
+        .enumerate()
+        .find(|(_, layer)| layer.identifier == layer_identifier)?;
+
+    let cell = LayerCell {
+        level: level_index,
+        layer: layer_index,
+        x: (x - level.world_x - layer.px_offset_x).div_euclid(layer.grid_size),
+        y: (y - level.world_y - layer.px_offset_y).div_euclid(layer.grid_size),
+    };
+    if !(0..layer.c_wid).contains(&cell.x) || !(0..layer.c_hei).contains(&cell.y) {
+        return None;
+    }
+
+    let (current, value) = if let Some(int_grid) = &layer.int_grid {
+        let current = *int_grid.get((cell.x + cell.y * layer.c_wid) as usize)?;
+        let value = match edit {
+            Edit::Paint => editor.int_grid_value,
+            Edit::Erase => 0,
+        };
+        (CellValue::IntGrid(current), CellValue::IntGrid(value))
+    } else if layer.tileset_uid.is_some() {
+        let px = [cell.x * layer.grid_size, cell.y * layer.grid_size];
+        let current = layer.tiles.iter().find(|tile| tile.px == px).copied();
+        let tile = match edit {
+            Edit::Paint => Some(TileInstance {
+                px,
+                id: editor.tile_id,
+                flip_x: false,
+                flip_y: false,
+                flip_d: false,
+            }),
+            Edit::Erase => None,
+        };
+        (CellValue::Tile(current), CellValue::Tile(tile))
+    } else {
+        return None;
+    };
+
+    let current = stroke.cells.get(&cell).copied().unwrap_or(current);
+    (current != value).then(|| (cell, value))
+}
+
+/// Sets the value of a cell in the project. Returns the UID of the level edited.
+fn apply_cell_change(project: &mut WorldProject, cell: LayerCell, value: CellValue) -> i64 {
+    let level = &mut project.levels[cell.level];
+    let layer = &mut level.layers.as_mut().unwrap()[cell.layer];
+    match value {
+        CellValue::IntGrid(value) => {
+            layer.int_grid.as_mut().unwrap()[(cell.x + cell.y * layer.c_wid) as usize] = value;
+        }
+        CellValue::Tile(tile) => {
+            let px = [cell.x * layer.grid_size, cell.y * layer.grid_size];
+            let existing = layer.tiles.iter().position(|tile| tile.px == px);
+            match (existing, tile) {
+                (Some(i), Some(tile)) => layer.tiles[i] = tile,
+                (None, Some(tile)) => layer.tiles.push(tile),
+                (Some(i), None) => {
+                    layer.tiles.remove(i);
+                }
+                (None, None) => (),
+            }
+        }
+    }
+
+    level.uid
+}
+
+/// Shows a tile change on the tilemap of its layer. IntGrid values aren't drawn.
+fn show_cell_change(
+    commands: &mut Commands,
+    map_query: &mut MapQuery,
+    project: &WorldProject,
+    cell: LayerCell,
+    value: CellValue,
+) {
+    let tile = if let CellValue::Tile(tile) = value {
+        tile
+    } else {
+        return;
+    };
+    let level = &project.levels[cell.level];
+    let layers = level.layers.as_ref().unwrap();
+    let layer = &layers[cell.layer];
+
+    // Same IDs and positions as when building the tilemaps in `process_loaded_tile_maps`
+    let (map_id, layer_id) = (level.uid as u16, (layers.len() - 1 - cell.layer) as u16);
+    let pos = TilePos(cell.x as u32, (layer.c_hei - cell.y - 1) as u32);
+    let updated = match tile {
+        Some(tile) => map_query
+            .set_tile(
+                commands,
+                pos,
+                Tile {
+                    texture_index: tile.id,
+                    flip_x: tile.flip_x,
+                    flip_y: tile.flip_y,
+                    flip_d: tile.flip_d,
+                    color: Color::rgba(1., 1., 1., layer.opacity),
+                    ..default()
+                },
+                map_id,
+                layer_id,
+            )
+            .is_ok(),
+        None => map_query
+            .despawn_tile(commands, pos, map_id, layer_id)
+            .is_ok(),
+    };
+    // Layers without a tileset image have no tilemap
+    if updated {
+        map_query.notify_chunk_for_tile(pos, map_id, layer_id);
+    }
+}
+
+/// Applies the edits of a stroke to its project.
+fn finish_stroke(
+    stroke: &mut Stroke,
+    maps: &mut Assets<WorldProject>,
+    dirty_levels: &mut HashSet<i64>,
+) {
+    if stroke.cells.is_empty() {
+        return;
+    }
+
+    let cells = std::mem::take(&mut stroke.cells);
+    if let Some(map) = maps.get_mut(&stroke.project) {
+        for (cell, value) in cells {
+            dirty_levels.insert(apply_cell_change(map, cell, value));
+        }
+    }
+}
+
+fn show_editor_window(
+    debug: Res<DebugMode>,
+    mut egui: ResMut<bevy_egui::EguiContext>,
+    mut editor: ResMut<TileEditor>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    registry: Res<WorldRegistry>,
+    asset_settings: Option<Res<AssetServerSettings>>,
+    current_room: Query<&CurrentRoom, With<Player>>,
+) {
+    if !debug.active {
+        return;
+    }
+
+    let (map, current_room) = match (maps.get(&world.project), current_room.get_single()) {
+        (Some(map), Ok(current_room)) => (map, current_room),
+        _ => return,
+    };
+    let layers: Vec<_> = map
+        .level(&current_room.id)
+        .and_then(|level| level.layers.as_ref())
+        .into_iter()
+        .flatten()
+        .filter(|layer| layer.int_grid.is_some() || layer.tileset_uid.is_some())
+        .collect();
+
+    egui::Window::new("Tile editor [debug]").show(egui.ctx_mut(), |ui| {
+        ui.checkbox(&mut editor.enabled, "Enabled");
+
+        egui::ComboBox::from_label("Layer")
+            .selected_text(editor.layer.clone().unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for layer in layers.iter() {
+                    ui.selectable_value(
+                        &mut editor.layer,
+                        Some(layer.identifier.clone()),
+                        &layer.identifier,
+                    );
+                }
+            });
+
+        let selected_layer = layers
+            .iter()
+            .find(|layer| Some(&layer.identifier) == editor.layer.as_ref());
+        match selected_layer {
+            Some(layer) if layer.int_grid.is_some() => {
+                ui.add(
+                    egui::DragValue::new(&mut editor.int_grid_value)
+                        .clamp_range(1..=999)
+                        .prefix("Value: "),
+                );
+            }
+            Some(_) => {
+                ui.add(egui::DragValue::new(&mut editor.tile_id).prefix("Tile ID: "));
+            }
+            None => {
+                ui.label("Select a layer of the current level");
+            }
+        }
+        ui.label("Left click to paint, right click to erase");
+
+        ui.separator();
+        ui.label(format!(
+            "{} level(s) with unsaved changes",
+            editor.dirty_levels.len()
+        ));
+        if ui.button("Save").clicked() {
+            let asset_folder = asset_settings
+                .as_ref()
+                .map_or("assets", |settings| settings.asset_folder.as_str());
+            let path =
+                Path::new(asset_folder).join(registry.current.as_deref().unwrap_or_default());
+            editor.status = Some(match save_levels(map, &path, &editor.dirty_levels) {
+                Ok(()) => {
+                    editor.dirty_levels.clear();
+                    format!("Saved to {}", path.display())
+                }
+                Err(err) => format!("Could not save: {}", err),
+            });
+        }
+        if let Some(status) = &editor.status {
+            ui.label(status);
+        }
+    });
+}
+
+fn paint_tiles(
+    mut commands: Commands,
+    debug: Res<DebugMode>,
+    mut editor: ResMut<TileEditor>,
+    mut egui: ResMut<bevy_egui::EguiContext>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    world: Res<GameWorld>,
+    mut maps: ResMut<Assets<WorldProject>>,
+    mut map_query: MapQuery,
+    camera: Query<(&GlobalTransform, &OrthographicProjection), With<Camera>>,
+    mut stroke: Local<Stroke>,
+) {
+    let editor = &mut *editor;
+    let edit = match (
+        mouse.pressed(MouseButton::Left),
+        mouse.pressed(MouseButton::Right),
+    ) {
+        (true, _) => Edit::Paint,
+        (_, true) => Edit::Erase,
+        _ => {
+            finish_stroke(&mut stroke, &mut maps, &mut editor.dirty_levels);
+            return;
+        }
+    };
+
+    if !debug.active || !editor.enabled || egui.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let layer = if let Some(layer) = editor.layer.clone() {
+        layer
+    } else {
+        return;
+    };
+
+    let position = match camera.get_single() {
+        Ok((transform, projection)) => cursor_world_position(&windows, transform, projection),
+        Err(_) => None,
+    };
+    let position = if let Some(position) = position {
+        position
+    } else {
+        return;
+    };
+
+    // Tile centers are at integer bevy coordinates, and LDtk coordinates are +Y down
+    const TILE_SIZE: f32 = crate::world::TILE_SIZE as f32;
+    let pixel = (
+        ((position.x + 0.5) * TILE_SIZE).floor() as i64,
+        ((-position.y + 0.5) * TILE_SIZE).floor() as i64,
+    );
+
+    // The cells of a stroke refer to the levels of the world it was started in
+    if stroke.project != world.project {
+        finish_stroke(&mut stroke, &mut maps, &mut editor.dirty_levels);
+        stroke.project = world.project.clone();
+    }
+
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+    if let Some((cell, value)) = find_cell_change(map, &layer, pixel, edit, editor, &stroke) {
+        show_cell_change(&mut commands, &mut map_query, map, cell, value);
+        stroke.cells.insert(cell, value);
+    }
+}
@@ -0,0 +1,179 @@
+//! Settings that change from level to level, applied whenever the player enters a level.
+//!
+//! They are read from the following LDtk level fields:
+//! - `Gravity` (Float): Multiplier applied to the player gravity. Defaults to 1.
+//! - `Camera_Zoom` (Float): Values over 1 show less of the level around the player. Defaults to 1.
+//! - `Music` (String): Track to loop, relative to the assets folder. Levels without one keep
+//!   playing the current track.
+//!
+//! The clear colour is taken from the level background colour, if it has been changed from the
+//! project default in LDtk.
+
+use bevy::{
+    audio::AudioSink,
+    prelude::*,
+    render::camera::{CameraProjection, OrthographicProjection},
+};
+
+use crate::{
+    level::Level,
+    player::CurrentRoom,
+    world::{world_changed, GameWorld},
+    Player, WorldProject,
+};
+
+/// Colour drawn behind levels that don't set their own background colour.
+const DEFAULT_CLEAR_COLOR: &str = "34202b";
+/// How quickly the camera zoom approaches the one of the current level, in 1/seconds.
+const ZOOM_SPEED: f32 = 8.;
+
+/// Settings of the level the player is in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelEnvironment {
+    pub gravity_scale: f32,
+    pub camera_zoom: f32,
+    pub music: Option<String>,
+    pub clear_color: Color,
+}
+
+impl Default for LevelEnvironment {
+    fn default() -> Self {
+        Self {
+            gravity_scale: 1.,
+            camera_zoom: 1.,
+            music: None,
+            clear_color: Color::hex(DEFAULT_CLEAR_COLOR).unwrap(),
+        }
+    }
+}
+
+impl LevelEnvironment {
+    /// Reads the environment of a level from its fields, using the defaults for those not set.
+    pub fn from_level(level: &Level) -> Self {
+        let default = Self::default();
+        let float = |identifier| {
+            level
+                .field(identifier)
+                .and_then(|value| value.as_f64())
+                .map(|value| value as f32)
+        };
+
+        Self {
+            gravity_scale: float("Gravity").unwrap_or(default.gravity_scale),
+            camera_zoom: float("Camera_Zoom")
+                .filter(|zoom| *zoom > 0.)
+                .unwrap_or(default.camera_zoom),
+            music: level
+                .field("Music")
+                .and_then(|value| value.as_str())
+                .map(str::to_owned),
+            clear_color: level
+                .bg_color
+                .as_deref()
+                .and_then(|color| Color::hex(color).ok())
+                .unwrap_or(default.clear_color),
+        }
+    }
+}
+
+/// The track being played, if any.
+#[derive(Default)]
+struct MusicPlayback {
+    track: Option<String>,
+    sink: Option<Handle<AudioSink>>,
+}
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelEnvironment>()
+            .init_resource::<MusicPlayback>()
+            .insert_resource(ClearColor(LevelEnvironment::default().clear_color))
+            .add_system(update_level_environment)
+            .add_system(apply_clear_color.after(update_level_environment))
+            .add_system(play_level_music.after(update_level_environment))
+            .add_system(zoom_camera.after(update_level_environment));
+    }
+}
+
+fn update_level_environment(
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    current_room: Query<&CurrentRoom, With<Player>>,
+    current_room_changed: Query<(), (With<Player>, Changed<CurrentRoom>)>,
+    mut environment: ResMut<LevelEnvironment>,
+) {
+    if !world_changed(&world, &mut map_events) && current_room_changed.is_empty() {
+        return;
+    }
+
+    let level = match (maps.get(&world.project), current_room.get_single()) {
+        (Some(map), Ok(current_room)) => map.level(&current_room.id),
+        _ => return,
+    };
+
+    if let Some(level) = level {
+        let new_environment = LevelEnvironment::from_level(level);
+        // Avoid triggering change detection, which would restart the music
+        if *environment != new_environment {
+            *environment = new_environment;
+        }
+    }
+}
+
+fn apply_clear_color(environment: Res<LevelEnvironment>, mut clear_color: ResMut<ClearColor>) {
+    if environment.is_changed() {
+        clear_color.0 = environment.clear_color;
+    }
+}
+
+fn play_level_music(
+    environment: Res<LevelEnvironment>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+    mut playback: ResMut<MusicPlayback>,
+) {
+    if !environment.is_changed()
+        || environment.music.is_none()
+        || environment.music == playback.track
+    {
+        return;
+    }
+
+    // Sinks can't be stopped in this version of bevy, so the previous track is paused instead
+    if let Some(sink) = playback.sink.take().and_then(|sink| sinks.get(&sink)) {
+        sink.pause();
+    }
+
+    if let Some(track) = &environment.music {
+        let sink = audio.play_with_settings(asset_server.load(track), PlaybackSettings::LOOP);
+        playback.sink = Some(sinks.get_handle(sink));
+        playback.track = Some(track.clone());
+    }
+}
+
+fn zoom_camera(
+    time: Res<Time>,
+    environment: Res<LevelEnvironment>,
+    mut camera: Query<(&mut Camera, &mut OrthographicProjection)>,
+) {
+    let target_scale = 1. / environment.camera_zoom;
+
+    for (mut camera, mut projection) in camera.iter_mut() {
+        if (projection.scale - target_scale).abs() < 0.001 {
+            if projection.scale != target_scale {
+                projection.scale = target_scale;
+                camera.projection_matrix = projection.get_projection_matrix();
+            }
+            continue;
+        }
+
+        let t = 1. - (-ZOOM_SPEED * time.delta_seconds()).exp();
+        projection.scale += (target_scale - projection.scale) * t;
+        // The projection matrix is only recomputed by bevy when the window changes
+        camera.projection_matrix = projection.get_projection_matrix();
+    }
+}
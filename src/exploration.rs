@@ -0,0 +1,71 @@
+//! Records which rooms the player has visited, so that maps can hide unexplored areas.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    player::{CurrentRoom, RoomPos},
+    Player,
+};
+
+/// Levels visited by the player, along with the [`RoomPos`] cells visited within each of them.
+/// Levels are listed per world, since their identifiers are only unique within a world.
+///
+/// Cells are only ever added, so saving this resource is enough to restore the fog of war of the
+/// maps.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ExploredRooms {
+    /// Visited cells by world path and level identifier.
+    pub worlds: HashMap<String, HashMap<String, HashSet<(i64, i64)>>>,
+}
+
+impl ExploredRooms {
+    fn cells(&self, world: &str, level: &str) -> Option<&HashSet<(i64, i64)>> {
+        self.worlds.get(world).and_then(|levels| levels.get(level))
+    }
+
+    pub fn is_level_explored(&self, world: &str, level: &str) -> bool {
+        self.cells(world, level).is_some()
+    }
+
+    pub fn is_cell_explored(&self, world: &str, level: &str, pos: &RoomPos) -> bool {
+        self.cells(world, level)
+            .map_or(false, |cells| cells.contains(&(pos.x, pos.y)))
+    }
+
+    /// Marks the given cell of a level as visited. Returns whether it had not been visited before.
+    pub fn record(&mut self, world: &str, level: &str, pos: &RoomPos) -> bool {
+        self.worlds
+            .entry(world.to_owned())
+            .or_default()
+            .entry(level.to_owned())
+            .or_default()
+            .insert((pos.x, pos.y))
+    }
+}
+
+pub struct ExplorationPlugin;
+
+impl Plugin for ExplorationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExploredRooms>()
+            .add_system(record_explored_rooms);
+    }
+}
+
+fn record_explored_rooms(
+    mut explored: ResMut<ExploredRooms>,
+    player: Query<
+        (&RoomPos, &CurrentRoom),
+        (With<Player>, Or<(Changed<RoomPos>, Changed<CurrentRoom>)>),
+    >,
+) {
+    for (room_pos, current_room) in player.iter() {
+        // Avoid triggering change detection if the cell was already explored
+        if !explored.is_cell_explored(&current_room.world, &current_room.id, room_pos) {
+            explored.record(&current_room.world, &current_room.id, room_pos);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Renders the collision map of a world to images and text, so that collision changes can be
+//! reviewed without opening LDtk.
+
+use std::fmt::Write;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    level::{EntityInstance, Level},
+    validation::START_POINT_IDENTIFIER,
+    world::{LevelTile, TileRect, TILE_SIZE},
+    WorldProject,
+};
+
+/// Colour and character used to draw each kind of tile. Tiles with several flags use the first
+/// match.
+const TILE_STYLES: [(LevelTile, Rgba<u8>, char); 3] = [
+    (LevelTile::SOLID, Rgba([0xe0, 0xe0, 0xe0, 0xff]), '#'),
+    (LevelTile::PLATFORM, Rgba([0x4a, 0x90, 0xe2, 0xff]), '='),
+    (LevelTile::HAZARD, Rgba([0x8a, 0xe2, 0x4a, 0xff]), '~'),
+];
+const EMPTY_COLOR: Rgba<u8> = Rgba([0x34, 0x20, 0x2b, 0xff]);
+const EMPTY_CHAR: char = '.';
+const ROOM_BOUNDARY_COLOR: Rgba<u8> = Rgba([0xe2, 0x4a, 0x4a, 0xff]);
+const START_POINT_COLOR: Rgba<u8> = Rgba([0x4a, 0xe2, 0x6a, 0xff]);
+const ENTITY_COLOR: Rgba<u8> = Rgba([0xe2, 0xc4, 0x4a, 0xff]);
+
+fn tile_style(tile: LevelTile) -> Option<(Rgba<u8>, char)> {
+    TILE_STYLES
+        .iter()
+        .find(|(flags, _, _)| tile.intersects(*flags))
+        .map(|&(_, color, c)| (color, c))
+}
+
+/// Returns the entities of a level along with their position in tiles, in world coordinates (+Y
+/// down).
+fn level_entities(level: &Level) -> impl Iterator<Item = (&EntityInstance, i64, i64)> {
+    let tile_size = TILE_SIZE as i64;
+    level.entities.iter().map(move |entity| {
+        (
+            entity,
+            (level.world_x + entity.px[0]).div_euclid(tile_size),
+            (level.world_y + entity.px[1]).div_euclid(tile_size),
+        )
+    })
+}
+
+/// Renders the collision map of every level to an image, using `scale` pixels per tile. Room
+/// boundaries and entities are drawn on top of the tiles.
+pub fn collision_image(project: &WorldProject, scale: u32) -> RgbaImage {
+    let bounds = match project
+        .levels
+        .iter()
+        .map(TileRect::of_level)
+        .reduce(TileRect::union)
+    {
+        Some(bounds) => bounds,
+        None => return RgbaImage::new(0, 0),
+    };
+
+    let mut image = RgbaImage::new(
+        bounds.width() as u32 * scale,
+        bounds.height() as u32 * scale,
+    );
+    let fill_tile = |image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>| {
+        let (px, py) = (
+            (x - bounds.min_x) as u32 * scale,
+            (y - bounds.min_y) as u32 * scale,
+        );
+        for dy in 0..scale {
+            for dx in 0..scale {
+                image.put_pixel(px + dx, py + dy, color);
+            }
+        }
+    };
+
+    for level in project.levels.iter() {
+        let rect = TileRect::of_level(level);
+        for y in rect.min_y..rect.max_y {
+            for x in rect.min_x..rect.max_x {
+                // get_tile takes bevy coordinates, which are +Y up
+                let color = tile_style(project.get_tile(x, -y)).map_or(EMPTY_COLOR, |s| s.0);
+                fill_tile(&mut image, x, y, color);
+            }
+        }
+    }
+
+    for level in project.levels.iter() {
+        let rect = TileRect::of_level(level);
+        // Levels smaller than a tile have no boundary to draw
+        if rect.width() <= 0 || rect.height() <= 0 || scale == 0 {
+            continue;
+        }
+        let (min_x, min_y) = (
+            (rect.min_x - bounds.min_x) as u32 * scale,
+            (rect.min_y - bounds.min_y) as u32 * scale,
+        );
+        let (max_x, max_y) = (
+            (rect.max_x - bounds.min_x) as u32 * scale - 1,
+            (rect.max_y - bounds.min_y) as u32 * scale - 1,
+        );
+        for x in min_x..=max_x {
+            image.put_pixel(x, min_y, ROOM_BOUNDARY_COLOR);
+            image.put_pixel(x, max_y, ROOM_BOUNDARY_COLOR);
+        }
+        for y in min_y..=max_y {
+            image.put_pixel(min_x, y, ROOM_BOUNDARY_COLOR);
+            image.put_pixel(max_x, y, ROOM_BOUNDARY_COLOR);
+        }
+
+        for (entity, x, y) in level_entities(level) {
+            if rect.contains(x, y) {
+                let color = if entity.identifier == START_POINT_IDENTIFIER {
+                    START_POINT_COLOR
+                } else {
+                    ENTITY_COLOR
+                };
+                fill_tile(&mut image, x, y, color);
+            }
+        }
+    }
+
+    image
+}
+
+/// Renders the collision map of every level as text, one framed grid per level. Entities are
+/// marked with the first letter of their identifier.
+pub fn collision_ascii(project: &WorldProject) -> String {
+    let mut output = String::new();
+
+    for level in project.levels.iter() {
+        let rect = TileRect::of_level(level);
+        let mut rows: Vec<Vec<char>> = (rect.min_y..rect.max_y)
+            .map(|y| {
+                (rect.min_x..rect.max_x)
+                    .map(|x| tile_style(project.get_tile(x, -y)).map_or(EMPTY_CHAR, |s| s.1))
+                    .collect()
+            })
+            .collect();
+
+        for (entity, x, y) in level_entities(level) {
+            if rect.contains(x, y) {
+                rows[(y - rect.min_y) as usize][(x - rect.min_x) as usize] =
+                    entity.identifier.chars().next().unwrap_or('?');
+            }
+        }
+
+        let border = format!("+{}+", "-".repeat(rect.width() as usize));
+        writeln!(
+            output,
+            "{} ({}, {}) {}x{}",
+            level.identifier,
+            rect.min_x,
+            rect.min_y,
+            rect.width(),
+            rect.height()
+        )
+        .unwrap();
+        writeln!(output, "{}", border).unwrap();
+        for row in rows {
+            writeln!(output, "|{}|", row.into_iter().collect::<String>()).unwrap();
+        }
+        writeln!(output, "{}", border).unwrap();
+        writeln!(output).unwrap();
+    }
+
+    output
+}
@@ -6,6 +6,8 @@ use bevy::sprite::Rect;
 pub struct CameraFollow {
     pub target: Option<Entity>,
     pub bounds: Rect,
+    /// Pan currently being played towards the position given by `bounds`, if any.
+    pub transition: Option<BoundsTransition>,
 }
 
 impl Default for CameraFollow {
@@ -16,10 +18,20 @@ impl Default for CameraFollow {
                 min: vec2(f32::NEG_INFINITY, f32::NEG_INFINITY),
                 max: vec2(f32::INFINITY, f32::INFINITY),
             },
+            transition: None,
         }
     }
 }
 
+/// Interpolates the camera position from `from` to the one given by the follow bounds. Whoever
+/// starts the transition is in charge of advancing `progress` and removing it once finished.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundsTransition {
+    pub from: Vec2,
+    /// Goes from 0 (At `from`) to 1 (At the target position).
+    pub progress: f32,
+}
+
 pub struct FollowPlugin;
 
 impl Plugin for FollowPlugin {
@@ -28,26 +40,53 @@ impl Plugin for FollowPlugin {
     }
 }
 
-fn follow(
+/// Returns the position the camera should be at to follow `target` without leaving its bounds.
+fn clamp_to_bounds(target: Vec2, bounds: &Rect, projection: &OrthographicProjection) -> Vec2 {
+    let clamp_axis = |target: f32, bounds_min: f32, bounds_max: f32, left: f32, right: f32| {
+        let min = bounds_min - left;
+        let max = bounds_max - right;
+        if max <= min {
+            (bounds_min + bounds_max) / 2.
+        } else {
+            target.clamp(min, max)
+        }
+    };
+
+    vec2(
+        clamp_axis(
+            target.x,
+            bounds.min.x,
+            bounds.max.x,
+            projection.left * projection.scale,
+            projection.right * projection.scale,
+        ),
+        clamp_axis(
+            target.y,
+            bounds.min.y,
+            bounds.max.y,
+            projection.bottom * projection.scale,
+            projection.top * projection.scale,
+        ),
+    )
+}
+
+pub fn follow(
     mut query: Query<(&mut Transform, &OrthographicProjection, &CameraFollow)>,
     target_q: Query<&Transform, Without<CameraFollow>>,
 ) {
     for (mut transform, projection, follow) in query.iter_mut() {
         if let Some(target_transform) = follow.target.and_then(|t| target_q.get(t).ok()) {
-            let min = follow.bounds.min.x - projection.left;
-            let max = follow.bounds.max.x - projection.right;
-            transform.translation.x = if max <= min {
-                (follow.bounds.min.x + follow.bounds.max.x) / 2.
-            } else {
-                target_transform.translation.x.clamp(min, max)
-            };
-            let min = follow.bounds.min.y - projection.bottom;
-            let max = follow.bounds.max.y - projection.top;
-            transform.translation.y = if max <= min {
-                (follow.bounds.min.y + follow.bounds.max.y) / 2.
-            } else {
-                target_transform.translation.y.clamp(min, max)
+            let position = clamp_to_bounds(
+                target_transform.translation.truncate(),
+                &follow.bounds,
+                projection,
+            );
+            let position = match follow.transition {
+                Some(transition) => transition.from.lerp(position, transition.progress),
+                None => position,
             };
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
         }
     }
 }
@@ -0,0 +1,1038 @@
+//! Procedural generation of worlds from hand-authored room templates.
+//!
+//! The levels of a template world are used as rooms. Starting from a level with a `Start_Point`
+//! entity, rooms are attached to each other through the openings in their edges: a run of
+//! non-solid tiles on one side of a room's `Collision` layer connects to a run of the same length
+//! on the opposite side of another room. Openings left without a neighbour are sealed with solid
+//! tiles, which only changes collisions, so templates should draw openings that still look right
+//! when closed.
+//!
+//! Layouts in which some room can't be reached from the start point with the jumps allowed by the
+//! player properties are discarded, see [`levels_out_of_reach`]. The same templates, seed and
+//! properties always produce the same world.
+
+use std::collections::{HashSet, VecDeque};
+
+use bevy::asset::LoadState;
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+use crate::{
+    level::{Level, LevelLayer, COLLISION_LAYER},
+    player::{PlayerProperties, TeleportPlayer},
+    util::Rng,
+    validation::START_POINT_IDENTIFIER,
+    warp::arrival_position,
+    world::{GameWorld, LevelTile, TileRect, WorldSource, TILE_SIZE},
+    worlds::WorldRegistry,
+    Player, WorldProject,
+};
+
+/// Layouts tried for a seed before giving up.
+const MAX_ATTEMPTS: u32 = 50;
+/// Rooms placed by worlds generated through the debug shortcut.
+const DEBUG_ROOM_COUNT: usize = 8;
+/// Time step of the movement simulation, in seconds. Matches the physics one.
+const SIMULATION_STEP: f32 = 0.016;
+/// Longest jump or fall simulated, in steps.
+const MAX_SIMULATION_STEPS: u32 = 600;
+/// Horizontal speeds tried while jumping and falling, as fractions of the maximum run speed.
+const SPEED_FRACTIONS: [f32; 7] = [-1., -0.5, -0.25, 0., 0.25, 0.5, 1.];
+/// Half the size of the player collider, slightly shrunk so that touching a wall doesn't count as
+/// overlapping it.
+const HALF_PLAYER_SIZE: f32 = 0.49;
+
+/// Generates a world from the rooms of a template world, and makes it the active one once done.
+/// The player is moved to its start point.
+#[derive(Debug, Clone)]
+pub struct GenerateWorld {
+    /// Path of the template world, relative to the assets folder.
+    pub templates: String,
+    pub seed: u64,
+    /// Rooms to place, including the starting one. Fewer are placed if the templates can't fit
+    /// any more.
+    pub room_count: usize,
+    /// Path to register the world under in the [`WorldRegistry`], replacing the world registered
+    /// there before. Defaults to the path of the templates followed by `#` and the seed.
+    pub path: Option<String>,
+}
+
+pub struct GeneratorPlugin;
+
+impl Plugin for GeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerateWorld>().add_system(generate_worlds);
+        #[cfg(debug_assertions)]
+        app.add_system(generate_debug_world.before(generate_worlds));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+
+    /// Returns the length of this side of a room of the given size, in tiles.
+    fn len(self, width: i64, height: i64) -> i64 {
+        match self {
+            Side::Left | Side::Right => height,
+            Side::Top | Side::Bottom => width,
+        }
+    }
+
+    /// Returns the `i`th tile along this side of a room of the given size, counting from the top
+    /// or left corner.
+    fn tile(self, i: i64, width: i64, height: i64) -> (i64, i64) {
+        match self {
+            Side::Left => (0, i),
+            Side::Right => (width - 1, i),
+            Side::Top => (i, 0),
+            Side::Bottom => (i, height - 1),
+        }
+    }
+}
+
+/// Run of non-solid tiles along a side of a room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Opening {
+    side: Side,
+    /// Tiles from the top or left corner of the room to the first tile of the run.
+    start: i64,
+    len: i64,
+}
+
+struct Template<'a> {
+    level: &'a Level,
+    width: i64,
+    height: i64,
+    openings: Vec<Opening>,
+}
+
+fn has_start_point(level: &Level) -> bool {
+    level
+        .entities
+        .iter()
+        .any(|entity| entity.identifier == START_POINT_IDENTIFIER)
+}
+
+fn is_solid(layer: &LevelLayer, (x, y): (i64, i64)) -> bool {
+    layer
+        .int_grid
+        .as_ref()
+        .and_then(|int_grid| int_grid.get((x + y * layer.c_wid) as usize))
+        .map_or(false, |&value| value != 0)
+}
+
+impl<'a> Template<'a> {
+    /// Returns `None` if the level has no collision layer covering it, since its openings can't be
+    /// found.
+    fn new(level: &'a Level) -> Option<Self> {
+        let layer = level.layer(COLLISION_LAYER).filter(|layer| {
+            layer.int_grid.is_some()
+                && layer.grid_size == TILE_SIZE as i64
+                && layer.c_wid * layer.grid_size == level.px_wid
+                && layer.c_hei * layer.grid_size == level.px_hei
+        })?;
+        let (width, height) = (layer.c_wid, layer.c_hei);
+
+        let mut openings = Vec::new();
+        for side in Side::ALL {
+            let len = side.len(width, height);
+            let mut run_start = None;
+            for i in 0..=len {
+                let open = i < len && !is_solid(layer, side.tile(i, width, height));
+                match (open, run_start) {
+                    (true, None) => run_start = Some(i),
+                    (false, Some(start)) => {
+                        openings.push(Opening {
+                            side,
+                            start,
+                            len: i - start,
+                        });
+                        run_start = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Some(Self {
+            level,
+            width,
+            height,
+            openings,
+        })
+    }
+}
+
+struct PlacedRoom {
+    template: usize,
+    rect: TileRect,
+    /// Openings of the template leading to another room.
+    connected: Vec<Opening>,
+}
+
+/// Attaches rooms to random openings of the rooms already placed until there are `room_count` of
+/// them, or no more can fit.
+fn generate_layout(
+    templates: &[Template],
+    start: usize,
+    room_count: usize,
+    rng: &mut Rng,
+) -> Vec<PlacedRoom> {
+    let mut rooms = vec![PlacedRoom {
+        template: start,
+        rect: TileRect {
+            min_x: 0,
+            min_y: 0,
+            max_x: templates[start].width,
+            max_y: templates[start].height,
+        },
+        connected: Vec::new(),
+    }];
+    let mut open: Vec<(usize, Opening)> = templates[start]
+        .openings
+        .iter()
+        .map(|&opening| (0, opening))
+        .collect();
+
+    while rooms.len() < room_count && !open.is_empty() {
+        let (room, opening) = open.swap_remove(rng.below(open.len()));
+        let from = rooms[room].rect;
+
+        // Only one room has a start point
+        let mut candidates: Vec<(usize, Opening)> = templates
+            .iter()
+            .enumerate()
+            .filter(|&(i, template)| i != start && !has_start_point(template.level))
+            .flat_map(|(i, template)| {
+                template
+                    .openings
+                    .iter()
+                    .filter(|other| {
+                        other.side == opening.side.opposite() && other.len == opening.len
+                    })
+                    .map(move |&other| (i, other))
+            })
+            .collect();
+        rng.shuffle(&mut candidates);
+
+        let placed = candidates.into_iter().find_map(|(template, other)| {
+            let (width, height) = (templates[template].width, templates[template].height);
+            let (x, y) = match opening.side {
+                Side::Left => (from.min_x - width, from.min_y + opening.start - other.start),
+                Side::Right => (from.max_x, from.min_y + opening.start - other.start),
+                Side::Top => (
+                    from.min_x + opening.start - other.start,
+                    from.min_y - height,
+                ),
+                Side::Bottom => (from.min_x + opening.start - other.start, from.max_y),
+            };
+            let rect = TileRect {
+                min_x: x,
+                min_y: y,
+                max_x: x + width,
+                max_y: y + height,
+            };
+            (!rooms.iter().any(|room| room.rect.intersects(&rect))).then(|| (template, other, rect))
+        });
+
+        if let Some((template, other, rect)) = placed {
+            rooms[room].connected.push(opening);
+            let index = rooms.len();
+            open.extend(
+                templates[template]
+                    .openings
+                    .iter()
+                    .filter(|&&opening| opening != other)
+                    .map(|&opening| (index, opening)),
+            );
+            rooms.push(PlacedRoom {
+                template,
+                rect,
+                connected: vec![other],
+            });
+        }
+    }
+
+    rooms
+}
+
+/// Appends a suffix to the IIDs of the entity references in a field value.
+fn suffix_entity_refs(value: &mut serde_json::Value, suffix: &str) {
+    match value {
+        serde_json::Value::Array(values) => {
+            for value in values.iter_mut() {
+                suffix_entity_refs(value, suffix);
+            }
+        }
+        serde_json::Value::Object(object) => {
+            if let Some(serde_json::Value::String(iid)) = object.get_mut("entityIid") {
+                iid.push_str(suffix);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Copies the template of every room to its place in a new world, sealing the openings that
+/// don't lead anywhere.
+fn build_world(
+    source: &WorldProject,
+    templates: &[Template],
+    rooms: &[PlacedRoom],
+) -> WorldProject {
+    let tile_size = TILE_SIZE as i64;
+    let levels = rooms
+        .iter()
+        .enumerate()
+        .map(|(i, room)| {
+            let template = &templates[room.template];
+            let mut level = template.level.clone();
+            // Templates can be used several times, so identifiers and IIDs are made unique
+            let suffix = format!("_{}", i);
+            level.identifier.push_str(&suffix);
+            level.uid = i as i64;
+            level.world_x = room.rect.min_x * tile_size;
+            level.world_y = room.rect.min_y * tile_size;
+
+            for entity in level.entities.iter_mut() {
+                entity.iid.push_str(&suffix);
+                for value in entity.fields.values_mut() {
+                    suffix_entity_refs(value, &suffix);
+                }
+            }
+
+            let collision = level
+                .layers
+                .iter_mut()
+                .flatten()
+                .find(|layer| layer.identifier == COLLISION_LAYER);
+            if let Some(layer) = collision {
+                let (width, height) = (layer.c_wid, layer.c_hei);
+                let int_grid = layer.int_grid.get_or_insert_with(Vec::new);
+                for opening in template
+                    .openings
+                    .iter()
+                    .filter(|opening| !room.connected.contains(opening))
+                {
+                    for i in opening.start..opening.start + opening.len {
+                        let (x, y) = opening.side.tile(i, width, height);
+                        if let Some(value) = int_grid.get_mut((x + y * width) as usize) {
+                            *value = 1;
+                        }
+                    }
+                }
+            }
+
+            level
+        })
+        .collect();
+
+    WorldProject {
+        levels,
+        tilesets: source.tilesets.clone(),
+        tile_animations: source.tile_animations.clone(),
+        world_grid: source.world_grid,
+        entity_definitions: source.entity_definitions.clone(),
+        source: WorldSource::Generated,
+    }
+}
+
+/// Lays out the levels of `templates` into a new world with up to `room_count` rooms, in which
+/// every room can be reached with the given player properties.
+pub fn generate_world(
+    templates: &WorldProject,
+    seed: u64,
+    room_count: usize,
+    properties: &PlayerProperties,
+) -> anyhow::Result<WorldProject> {
+    let templates_with_openings: Vec<Template> = templates
+        .levels
+        .iter()
+        .filter_map(|level| {
+            let template = Template::new(level);
+            if template.is_none() {
+                warn!(
+                    "Level '{}' has no {} layer covering it, so it can't be used as a room",
+                    level.identifier, COLLISION_LAYER
+                );
+            }
+            template
+        })
+        .collect();
+    let start_templates: Vec<usize> = templates_with_openings
+        .iter()
+        .enumerate()
+        .filter(|(_, template)| has_start_point(template.level))
+        .map(|(i, _)| i)
+        .collect();
+    if start_templates.is_empty() {
+        anyhow::bail!("No room has a {} entity", START_POINT_IDENTIFIER);
+    }
+
+    let mut rng = Rng::new(seed);
+    for attempt in 1..=MAX_ATTEMPTS {
+        let start = start_templates[rng.below(start_templates.len())];
+        let rooms = generate_layout(&templates_with_openings, start, room_count, &mut rng);
+        let world = build_world(templates, &templates_with_openings, &rooms);
+
+        let out_of_reach = levels_out_of_reach(&world, properties).len();
+        if out_of_reach == 0 {
+            info!(
+                "Generated a world with {} room(s) from seed {} after {} attempt(s)",
+                rooms.len(),
+                seed,
+                attempt
+            );
+            return Ok(world);
+        }
+        debug!(
+            "Discarding layout {} of seed {}: {} room(s) can't be reached",
+            attempt, seed, out_of_reach
+        );
+    }
+
+    anyhow::bail!(
+        "No layout where every room can be reached was found in {} attempts",
+        MAX_ATTEMPTS
+    )
+}
+
+/// Collisions of a whole world, copied to a grid so they can be looked up quickly.
+struct CollisionGrid {
+    rect: TileRect,
+    tiles: Vec<LevelTile>,
+}
+
+impl CollisionGrid {
+    fn new(project: &WorldProject) -> Option<Self> {
+        let rect = project
+            .levels
+            .iter()
+            .map(TileRect::of_level)
+            .reduce(TileRect::union)?;
+        let tiles = (rect.min_y..rect.max_y)
+            .flat_map(|y| (rect.min_x..rect.max_x).map(move |x| (x, y)))
+            // get_tile takes bevy coordinates, which are +Y up
+            .map(|(x, y)| project.get_tile(x, -y))
+            .collect();
+
+        Some(Self { rect, tiles })
+    }
+
+    fn get(&self, x: i64, y: i64) -> LevelTile {
+        if self.rect.contains(x, y) {
+            let (x, y) = (x - self.rect.min_x, y - self.rect.min_y);
+            self.tiles[(x + y * self.rect.width()) as usize]
+        } else {
+            LevelTile::empty()
+        }
+    }
+
+    /// Returns whether the player overlaps a solid tile at the given position.
+    fn is_blocked(&self, position: Vec2) -> bool {
+        let (min, max) = (position - HALF_PLAYER_SIZE, position + HALF_PLAYER_SIZE);
+        (min.y.floor() as i64..=max.y.floor() as i64).any(|y| {
+            (min.x.floor() as i64..=max.x.floor() as i64)
+                .any(|x| self.get(x, y).contains(LevelTile::SOLID))
+        })
+    }
+
+    /// Returns the first row of solid or platform tiles the player lands on when moving down from
+    /// `from` to `to`.
+    fn ground_row(&self, from: Vec2, to: Vec2) -> Option<i64> {
+        let (min_x, max_x) = (
+            (to.x - HALF_PLAYER_SIZE).floor() as i64,
+            (to.x + HALF_PLAYER_SIZE).floor() as i64,
+        );
+        let first_row = (from.y + HALF_PLAYER_SIZE).floor() as i64 + 1;
+        let last_row = (to.y + HALF_PLAYER_SIZE).floor() as i64;
+
+        (first_row..=last_row).find(|&y| {
+            (min_x..=max_x).any(|x| {
+                self.get(x, y)
+                    .intersects(LevelTile::SOLID | LevelTile::PLATFORM)
+            })
+        })
+    }
+}
+
+/// Simulates how the player moves through a world, in tiles. Unlike in bevy, +Y is down, and the
+/// center of the tile `(x, y)` is at `(x + 0.5, y + 0.5)`.
+struct Movement<'a> {
+    grid: &'a CollisionGrid,
+    properties: &'a PlayerProperties,
+}
+
+fn cell_center((x, y): (i64, i64)) -> Vec2 {
+    vec2(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+/// Where a jump, fall or step ends on the ground.
+struct Landing {
+    /// Tile the player stands in once landed.
+    tile: (i64, i64),
+    /// Tiles the player went through on the way, excluding the landing one.
+    path: Vec<(i64, i64)>,
+}
+
+impl Movement<'_> {
+    /// Moves the player from `position` with a fixed horizontal speed and jump held, using the
+    /// extra jumps at the top of the previous one.
+    ///
+    /// Returns where the player lands, if they do.
+    fn simulate(
+        &self,
+        mut position: Vec2,
+        mut velocity: Vec2,
+        extra_jumps: u32,
+    ) -> Option<Landing> {
+        let properties = self.properties;
+        let mut jumps_used = 0;
+        let mut path = Vec::new();
+
+        for _ in 0..MAX_SIMULATION_STEPS {
+            velocity.y += if velocity.y < 0. {
+                properties.jump_gravity
+            } else {
+                properties.gravity
+            } * SIMULATION_STEP;
+            velocity.y = velocity
+                .y
+                .clamp(-properties.terminal_speed, properties.terminal_speed);
+
+            if velocity.y >= 0. && jumps_used < extra_jumps {
+                jumps_used += 1;
+                velocity.y = -properties.jump_force
+                    * properties.multijump_coefficient.powi(jumps_used as i32);
+            }
+
+            let next = position + vec2(velocity.x * SIMULATION_STEP, 0.);
+            if self.grid.is_blocked(next) {
+                velocity.x = 0.;
+            } else {
+                position = next;
+            }
+
+            let next = position + vec2(0., velocity.y * SIMULATION_STEP);
+            if velocity.y > 0. {
+                if let Some(row) = self.grid.ground_row(position, next) {
+                    return Some(Landing {
+                        tile: (position.x.floor() as i64, row - 1),
+                        path,
+                    });
+                }
+            } else if self.grid.is_blocked(next) {
+                velocity.y = 0.;
+                continue;
+            }
+            position = next;
+
+            let tile = (position.x.floor() as i64, position.y.floor() as i64);
+            if !self.grid.rect.contains(tile.0, tile.1) {
+                // Left the world
+                return None;
+            }
+            if path.last() != Some(&tile) {
+                path.push(tile);
+            }
+        }
+
+        None
+    }
+
+    /// Returns where the player can land when starting from the given tile.
+    fn moves_from(&self, tile: (i64, i64)) -> Vec<Landing> {
+        let properties = self.properties;
+        let ground = LevelTile::SOLID | LevelTile::PLATFORM;
+        let mut landings = Vec::new();
+
+        for extra_jumps in 0..properties.jumps_available {
+            for fraction in SPEED_FRACTIONS {
+                landings.extend(self.simulate(
+                    cell_center(tile),
+                    vec2(fraction * properties.max_run_speed, -properties.jump_force),
+                    extra_jumps,
+                ));
+            }
+        }
+
+        // Walking to either side, falling if there is no ground there. Jumps left after walking
+        // off a ledge can still be used.
+        for side in [-1, 1] {
+            let next = (tile.0 + side, tile.1);
+            if self.grid.get(next.0, next.1).contains(LevelTile::SOLID) {
+                continue;
+            }
+            if self.grid.get(next.0, next.1 + 1).intersects(ground) {
+                landings.push(Landing {
+                    tile: next,
+                    path: Vec::new(),
+                });
+                continue;
+            }
+
+            for extra_jumps in 0..properties.jumps_available.max(1) {
+                for fraction in SPEED_FRACTIONS
+                    .into_iter()
+                    .filter(|&fraction| fraction >= 0.)
+                {
+                    landings.extend(
+                        self.simulate(
+                            cell_center(next),
+                            vec2(side as f32 * fraction * properties.max_run_speed, 0.),
+                            extra_jumps,
+                        )
+                        .map(|mut landing| {
+                            landing.path.insert(0, next);
+                            landing
+                        }),
+                    );
+                }
+            }
+        }
+
+        // Dropping through a platform
+        if self.grid.get(tile.0, tile.1 + 1) == LevelTile::PLATFORM {
+            landings.extend(self.simulate(cell_center((tile.0, tile.1 + 1)), Vec2::ZERO, 0));
+        }
+
+        landings
+    }
+}
+
+/// Returns the levels the player can't get into from the start point, simulating the jumps and
+/// falls allowed by `properties`. Wall jumps aren't taken into account.
+///
+/// A level is reached if the player can land in it, or go through it on the way from one level to
+/// land in another, e.g. when falling down a shaft. Levels only entered mid-jump before falling
+/// back don't count.
+pub fn levels_out_of_reach<'a>(
+    project: &'a WorldProject,
+    properties: &PlayerProperties,
+) -> Vec<&'a Level> {
+    let grid = if let Some(grid) = CollisionGrid::new(project) {
+        grid
+    } else {
+        return Vec::new();
+    };
+    let start = if let Some((level, entity)) = project.find_entity(START_POINT_IDENTIFIER) {
+        let position = arrival_position(level, entity);
+        (position.x.round() as i64, (-position.y).round() as i64)
+    } else {
+        return project.levels.iter().collect();
+    };
+
+    let level_rects: Vec<TileRect> = project.levels.iter().map(TileRect::of_level).collect();
+    let level_at = |(x, y): (i64, i64)| level_rects.iter().position(|rect| rect.contains(x, y));
+    let mut reached_levels: HashSet<usize> = level_at(start).into_iter().collect();
+    let mut record = |from: (i64, i64), landing: &Landing| {
+        let landing_level = level_at(landing.tile);
+        reached_levels.extend(landing_level);
+        if landing_level != level_at(from) {
+            reached_levels.extend(landing.path.iter().filter_map(|&tile| level_at(tile)));
+        }
+    };
+
+    let movement = Movement {
+        grid: &grid,
+        properties,
+    };
+    let mut reached = HashSet::new();
+    let mut to_visit = VecDeque::new();
+    // The start point may be in the air, so the player falls from it first
+    if let Some(landing) = movement.simulate(cell_center(start), Vec2::ZERO, 0) {
+        record(start, &landing);
+        reached.insert(landing.tile);
+        to_visit.push_back(landing.tile);
+    }
+
+    while let Some(tile) = to_visit.pop_front() {
+        for landing in movement.moves_from(tile) {
+            record(tile, &landing);
+            if reached.insert(landing.tile) {
+                to_visit.push_back(landing.tile);
+            }
+        }
+    }
+
+    project
+        .levels
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !reached_levels.contains(i))
+        .map(|(_, level)| level)
+        .collect()
+}
+
+/// Generates the requested worlds once their templates have loaded, and switches to them.
+fn generate_worlds(
+    mut requests: EventReader<GenerateWorld>,
+    mut pending: Local<Vec<GenerateWorld>>,
+    asset_server: Res<AssetServer>,
+    mut registry: ResMut<WorldRegistry>,
+    mut maps: ResMut<Assets<WorldProject>>,
+    mut world: ResMut<GameWorld>,
+    player: Query<&Player>,
+    mut teleport_events: EventWriter<TeleportPlayer>,
+) {
+    pending.extend(requests.iter().cloned());
+    if pending.is_empty() {
+        return;
+    }
+
+    let default_properties = PlayerProperties::default();
+    let properties = player
+        .get_single()
+        .map_or(&default_properties, Player::properties);
+
+    let mut still_pending = Vec::new();
+    for request in pending.drain(..) {
+        let handle = registry.load(&asset_server, &request.templates);
+        let templates = if let Some(templates) = maps.get(&handle) {
+            templates
+        } else {
+            if asset_server.get_load_state(&handle) == LoadState::Failed {
+                error!("Could not load room templates '{}'", request.templates);
+            } else {
+                still_pending.push(request);
+            }
+            continue;
+        };
+
+        let project = match generate_world(templates, request.seed, request.room_count, properties)
+        {
+            Ok(project) => project,
+            Err(err) => {
+                error!(
+                    "Could not generate a world from '{}': {}",
+                    request.templates, err
+                );
+                continue;
+            }
+        };
+        let start = project
+            .find_entity(START_POINT_IDENTIFIER)
+            .map(|(level, entity)| arrival_position(level, entity));
+
+        let path = request
+            .path
+            .unwrap_or_else(|| format!("{}#{}", request.templates, request.seed));
+        let handle = maps.add(project);
+        registry.worlds.insert(path.clone(), handle.clone());
+        registry.current = Some(path);
+        world.project = handle;
+
+        if let Some(position) = start {
+            teleport_events.send(TeleportPlayer { position });
+        }
+    }
+    *pending = still_pending;
+}
+
+/// Generates a world from the rooms of the current one when pressing G in debug mode. Each one
+/// replaces the last, so that they don't pile up in memory.
+#[cfg(debug_assertions)]
+fn generate_debug_world(
+    keys: Res<Input<KeyCode>>,
+    debug: Res<crate::debug::DebugMode>,
+    time: Res<Time>,
+    registry: Res<WorldRegistry>,
+    mut generate_events: EventWriter<GenerateWorld>,
+) {
+    if !debug.active || !keys.just_pressed(KeyCode::G) {
+        return;
+    }
+
+    if let Some(current) = &registry.current {
+        let templates = current.split('#').next().unwrap_or(current);
+        generate_events.send(GenerateWorld {
+            templates: templates.to_owned(),
+            seed: time.time_since_startup().as_nanos() as u64,
+            room_count: DEBUG_ROOM_COUNT,
+            path: Some(format!("{}#debug", templates)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::level::EntityInstance;
+
+    /// Builds a level from rows of tiles: `#` is solid, `S` is the start point and anything else is
+    /// empty. `position` is the top left corner of the level, in tiles.
+    fn level(identifier: &str, position: (i64, i64), rows: &[&str]) -> Level {
+        let tile_size = TILE_SIZE as i64;
+        let (width, height) = (rows[0].len() as i64, rows.len() as i64);
+        let mut entities = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            for x in row.match_indices('S').map(|(x, _)| x) {
+                entities.push(EntityInstance {
+                    identifier: START_POINT_IDENTIFIER.to_owned(),
+                    iid: format!("{}_start", identifier),
+                    px: [x as i64 * tile_size, y as i64 * tile_size],
+                    width: tile_size,
+                    height: tile_size,
+                    pivot: Vec2::ZERO,
+                    fields: HashMap::new(),
+                });
+            }
+        }
+
+        Level {
+            identifier: identifier.to_owned(),
+            uid: 0,
+            world_x: position.0 * tile_size,
+            world_y: position.1 * tile_size,
+            px_wid: width * tile_size,
+            px_hei: height * tile_size,
+            bg_color: None,
+            fields: HashMap::new(),
+            layers: Some(vec![LevelLayer {
+                identifier: COLLISION_LAYER.to_owned(),
+                grid_size: tile_size,
+                c_wid: width,
+                c_hei: height,
+                px_offset_x: 0,
+                px_offset_y: 0,
+                opacity: 1.,
+                parallax_factor: Vec2::ZERO,
+                int_grid: Some(
+                    rows.iter()
+                        .flat_map(|row| row.chars().map(|c| (c == '#') as i64))
+                        .collect(),
+                ),
+                tileset_uid: None,
+                tiles: Vec::new(),
+            }]),
+            entities,
+        }
+    }
+
+    fn world(levels: Vec<Level>) -> WorldProject {
+        WorldProject {
+            levels,
+            tilesets: HashMap::new(),
+            tile_animations: HashMap::new(),
+            world_grid: Some((256, 256)),
+            entity_definitions: None,
+            source: WorldSource::Generated,
+        }
+    }
+
+    fn out_of_reach(project: &WorldProject) -> Vec<&str> {
+        levels_out_of_reach(project, &PlayerProperties::default())
+            .into_iter()
+            .map(|level| level.identifier.as_str())
+            .collect()
+    }
+
+    const CROSS: [&str; 6] = ["##..##", "#....#", "......", "......", "#....#", "##..##"];
+    const CROSS_START: [&str; 6] = ["##..##", "#....#", "..S...", "......", "#....#", "##..##"];
+
+    fn layout_summary(rooms: &[PlacedRoom]) -> Vec<(usize, TileRect)> {
+        rooms
+            .iter()
+            .map(|room| (room.template, room.rect))
+            .collect()
+    }
+
+    #[test]
+    fn layout_chains_rooms_through_matching_openings() {
+        let start = level(
+            "Start",
+            (0, 0),
+            &["######", "#.....", "#.....", "#.....", "#S....", "######"],
+        );
+        let corridor = level(
+            "Corridor",
+            (0, 0),
+            &["######", "......", "......", "......", "......", "######"],
+        );
+        let templates: Vec<Template> = [&start, &corridor]
+            .into_iter()
+            .map(|level| Template::new(level).unwrap())
+            .collect();
+
+        let rooms = generate_layout(&templates, 0, 4, &mut Rng::new(1));
+
+        let summary: Vec<(usize, i64, i64)> = rooms
+            .iter()
+            .map(|room| (room.template, room.rect.min_x, room.rect.min_y))
+            .collect();
+        assert_eq!(summary, [(0, 0, 0), (1, 6, 0), (1, 12, 0), (1, 18, 0)]);
+    }
+
+    #[test]
+    fn layout_is_the_same_for_the_same_seed() {
+        let (start, cross) = (
+            level("Start", (0, 0), &CROSS_START),
+            level("Cross", (0, 0), &CROSS),
+        );
+        let templates: Vec<Template> = [&start, &cross]
+            .into_iter()
+            .map(|level| Template::new(level).unwrap())
+            .collect();
+
+        let first = generate_layout(&templates, 0, 8, &mut Rng::new(42));
+        let second = generate_layout(&templates, 0, 8, &mut Rng::new(42));
+
+        assert_eq!(layout_summary(&first), layout_summary(&second));
+    }
+
+    #[test]
+    fn layout_rooms_do_not_overlap() {
+        let (start, cross) = (
+            level("Start", (0, 0), &CROSS_START),
+            level("Cross", (0, 0), &CROSS),
+        );
+        let templates: Vec<Template> = [&start, &cross]
+            .into_iter()
+            .map(|level| Template::new(level).unwrap())
+            .collect();
+
+        for seed in 0..20 {
+            let rooms = generate_layout(&templates, 0, 8, &mut Rng::new(seed));
+            assert!(rooms.len() > 1 && rooms.len() <= 8);
+            for (i, room) in rooms.iter().enumerate() {
+                for other in rooms[i + 1..].iter() {
+                    assert!(!room.rect.intersects(&other.rect), "seed {}", seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn walkable_levels_are_reached() {
+        let project = world(vec![
+            level(
+                "A",
+                (0, 0),
+                &[
+                    "##########",
+                    "#.........",
+                    "#.........",
+                    "#.........",
+                    "#S........",
+                    "##########",
+                ],
+            ),
+            level(
+                "B",
+                (10, 0),
+                &[
+                    "##########",
+                    ".........#",
+                    ".........#",
+                    ".........#",
+                    ".........#",
+                    "##########",
+                ],
+            ),
+        ]);
+
+        assert!(out_of_reach(&project).is_empty());
+    }
+
+    #[test]
+    fn enclosed_levels_are_out_of_reach() {
+        let closed = [
+            "##########",
+            "#........#",
+            "#........#",
+            "#........#",
+            "#........#",
+            "##########",
+        ];
+        let mut start = closed;
+        start[4] = "#S.......#";
+        let project = world(vec![
+            level("A", (0, 0), &start),
+            level("C", (0, -10), &closed),
+        ]);
+
+        assert_eq!(out_of_reach(&project), ["C"]);
+    }
+
+    #[test]
+    fn levels_only_entered_mid_jump_are_out_of_reach() {
+        let project = world(vec![
+            level(
+                "A",
+                (0, 0),
+                &[
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "#...S....#",
+                    "##########",
+                ],
+            ),
+            // No ground to land on, the player can only jump into it and fall back down
+            level(
+                "B",
+                (0, -4),
+                &["##########", "#........#", "#........#", "#........#"],
+            ),
+        ]);
+
+        assert_eq!(out_of_reach(&project), ["B"]);
+    }
+
+    #[test]
+    fn levels_fallen_through_are_reached() {
+        let project = world(vec![
+            level(
+                "A",
+                (0, 0),
+                &[
+                    "##########",
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "#S.......#",
+                    "####..####",
+                ],
+            ),
+            level(
+                "Shaft",
+                (0, 6),
+                &["#........#", "#........#", "#........#", "#........#"],
+            ),
+            level(
+                "C",
+                (0, 10),
+                &[
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "#........#",
+                    "##########",
+                ],
+            ),
+        ]);
+
+        assert!(out_of_reach(&project).is_empty());
+    }
+}
@@ -22,12 +22,15 @@ pub enum Action {
     Jump,
     Pause,
     Down,
+    Interact,
+    Dash,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Deserialize)]
 #[non_exhaustive]
 pub enum Axis {
     Horizontal,
+    Vertical,
 }
 
 /// Triggers that have a state defined by an [ActionState] value.
@@ -269,6 +272,12 @@ impl Default for InputMappings {
                 ),
                 Action::Down => ActionBinding::new(
                     DigitalTrigger::Key(KeyCode::Down), Some(DigitalTrigger::GamepadButton(GamepadButton(Gamepad(0),GamepadButtonType::DPadDown)))
+                ),
+                Action::Interact => ActionBinding::new(
+                    DigitalTrigger::Key(KeyCode::Up), Some(DigitalTrigger::GamepadButton(GamepadButton(Gamepad(0),GamepadButtonType::West)))
+                ),
+                Action::Dash => ActionBinding::new(
+                    DigitalTrigger::Key(KeyCode::LShift), Some(DigitalTrigger::GamepadButton(GamepadButton(Gamepad(0),GamepadButtonType::RightTrigger)))
                 )
             },
             axes: enum_map! {
@@ -279,6 +288,13 @@ impl Default for InputMappings {
                     },
                     None
                 ),
+                Axis::Vertical => AxisBinding::new(
+                    AnalogTrigger::DigitalJoystick{
+                        negative: DigitalTrigger::Key(KeyCode::S),
+                        positive: DigitalTrigger::Key(KeyCode::W),
+                    },
+                    None
+                ),
             },
         }
     }
@@ -0,0 +1,502 @@
+//! Loading of LDtk projects (`.ldtk`) and of the level files used by projects saved with the
+//! "separate level files" option (`.ldtkl`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use bevy::asset::{AssetLoader, AssetPath, LoadedAsset};
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Serialize;
+
+use crate::{
+    level::{EntityInstance, Level, LevelLayer, TileInstance, Tileset},
+    tile_animation::TileAnimation,
+    world::{WorldProject, WorldSource},
+};
+
+/// Data of an LDtk project needed to convert its levels again when they are reloaded, or to save
+/// them back.
+pub struct LdtkSource {
+    pub defs: ldtk_rust::Definitions,
+    /// Levels saved in separate `.ldtkl` files, indexed by level identifier. Their contents are
+    /// already part of the project, the handles are only kept to hot-reload them.
+    pub external_levels: HashMap<String, ExternalLevel>,
+}
+
+pub struct ExternalLevel {
+    /// Path of the level file, relative to the project.
+    pub rel_path: String,
+    pub handle: Handle<LdtkLevel>,
+}
+
+/// A single level saved in its own file, for projects using LDtk's "separate level files" option.
+#[derive(TypeUuid)]
+#[uuid = "0d5d1c9e-8a6f-4c43-9f5e-3f2f2a8e2b71"]
+pub struct LdtkLevel {
+    pub level: ldtk_rust::Level,
+}
+
+pub struct LdtkLevelLoader;
+
+impl AssetLoader for LdtkLevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level: ldtk_rust::Level = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(LdtkLevel { level }));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ldtkl"]
+    }
+}
+
+/// Reads the animations defined in the custom data of the tiles of a project.
+fn parse_tile_animations(defs: &ldtk_rust::Definitions) -> HashMap<(i64, u16), TileAnimation> {
+    defs.tilesets
+        .iter()
+        .flat_map(|tileset| {
+            tileset.custom_data.iter().filter_map(|data| {
+                match ron::de::from_str::<TileAnimation>(&data.data) {
+                    Ok(animation) => Some(((tileset.uid, data.tile_id as u16), animation)),
+                    Err(err) => {
+                        warn!(
+                            "Invalid animation for tile {} of tileset '{}': {}",
+                            data.tile_id, tileset.identifier, err
+                        );
+                        None
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Collects the fields that have been set into a map.
+fn convert_fields(fields: &[ldtk_rust::FieldInstance]) -> HashMap<String, serde_json::Value> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            field
+                .value
+                .clone()
+                .filter(|value| !value.is_null())
+                .map(|value| (field.identifier.clone(), value))
+        })
+        .collect()
+}
+
+fn convert_entity(entity: &ldtk_rust::EntityInstance) -> EntityInstance {
+    EntityInstance {
+        identifier: entity.identifier.clone(),
+        iid: entity.iid.clone(),
+        px: [entity.px[0], entity.px[1]],
+        width: entity.width,
+        height: entity.height,
+        pivot: vec2(entity.pivot[0] as f32, entity.pivot[1] as f32),
+        fields: convert_fields(&entity.field_instances),
+    }
+}
+
+fn convert_layer(layer: &ldtk_rust::LayerInstance, defs: &ldtk_rust::Definitions) -> LevelLayer {
+    LevelLayer {
+        identifier: layer.identifier.clone(),
+        grid_size: layer.grid_size,
+        c_wid: layer.c_wid,
+        c_hei: layer.c_hei,
+        px_offset_x: layer.px_total_offset_x,
+        px_offset_y: layer.px_total_offset_y,
+        opacity: layer.opacity as f32,
+        parallax_factor: defs
+            .layers
+            .iter()
+            .find(|def| def.uid == layer.layer_def_uid)
+            .map_or(Vec2::ZERO, |def| {
+                vec2(def.parallax_factor_x as f32, def.parallax_factor_y as f32)
+            }),
+        int_grid: (layer.layer_instance_type == "IntGrid").then(|| layer.int_grid_csv.clone()),
+        tileset_uid: layer.tileset_def_uid,
+        tiles: layer
+            .auto_layer_tiles
+            .iter()
+            .chain(layer.grid_tiles.iter())
+            .map(|tile| TileInstance {
+                px: [tile.px[0], tile.px[1]],
+                id: tile.t as u16,
+                // Bit 0 is X flip, bit 1 is Y flip
+                flip_x: tile.f & 0b01 != 0,
+                flip_y: tile.f & 0b10 != 0,
+                flip_d: false,
+            })
+            .collect(),
+    }
+}
+
+pub fn convert_level(level: &ldtk_rust::Level, defs: &ldtk_rust::Definitions) -> Level {
+    Level {
+        identifier: level.identifier.clone(),
+        uid: level.uid,
+        world_x: level.world_x,
+        world_y: level.world_y,
+        px_wid: level.px_wid,
+        px_hei: level.px_hei,
+        bg_color: level
+            .level_bg_color
+            .as_deref()
+            .map(|color| color.trim_start_matches('#').to_owned()),
+        fields: convert_fields(&level.field_instances),
+        layers: level.layer_instances.as_ref().map(|layers| {
+            layers
+                .iter()
+                .filter(|layer| layer.layer_instance_type != "Entities")
+                .map(|layer| convert_layer(layer, defs))
+                .collect()
+        }),
+        entities: level
+            .layer_instances
+            .iter()
+            .flatten()
+            .flat_map(|layer| layer.entity_instances.iter())
+            .map(convert_entity)
+            .collect(),
+    }
+}
+
+/// Converts a project whose external levels have already been merged into it. Tileset textures
+/// are left for the caller to set.
+fn convert_project(
+    project: ldtk_rust::Project,
+    external_levels: HashMap<String, ExternalLevel>,
+) -> WorldProject {
+    let defs = project.defs;
+    WorldProject {
+        levels: project
+            .levels
+            .iter()
+            .map(|level| convert_level(level, &defs))
+            .collect(),
+        tilesets: defs
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                (
+                    tileset.uid,
+                    Tileset {
+                        identifier: tileset.identifier.clone(),
+                        tile_size: tileset.tile_grid_size,
+                        px_wid: tileset.px_wid,
+                        px_hei: tileset.px_hei,
+                        spacing: tileset.spacing,
+                        padding: tileset.padding,
+                        texture: None,
+                    },
+                )
+            })
+            .collect(),
+        tile_animations: parse_tile_animations(&defs),
+        world_grid: project.world_grid_width.zip(project.world_grid_height),
+        entity_definitions: Some(
+            defs.entities
+                .iter()
+                .map(|def| def.identifier.clone())
+                .collect(),
+        ),
+        source: WorldSource::Ldtk(LdtkSource {
+            defs,
+            external_levels,
+        }),
+    }
+}
+
+pub struct LdtkLoader;
+
+impl AssetLoader for LdtkLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut project: ldtk_rust::Project = serde_json::from_slice(bytes)?;
+
+            let mut external_levels = Vec::new();
+            for level in project.levels.iter_mut() {
+                let rel_path = match &level.external_rel_path {
+                    Some(rel_path) => rel_path.clone(),
+                    None => continue,
+                };
+                let path: AssetPath = load_context.path().parent().unwrap().join(&rel_path).into();
+                let level_bytes = load_context.read_asset_bytes(path.path()).await?;
+                *level = serde_json::from_slice(&level_bytes)?;
+                external_levels.push((level.identifier.clone(), rel_path, path));
+            }
+
+            let dependencies: Vec<(i64, AssetPath)> = project
+                .defs
+                .tilesets
+                .iter()
+                .filter_map(|tileset| {
+                    tileset.rel_path.as_ref().map(|path| {
+                        (
+                            tileset.uid,
+                            load_context
+                                .path()
+                                .parent()
+                                .unwrap()
+                                .join(path.clone())
+                                .into(),
+                        )
+                    })
+                })
+                .collect();
+
+            let mut world = convert_project(
+                project,
+                external_levels
+                    .iter()
+                    .map(|(identifier, rel_path, path)| {
+                        (
+                            identifier.clone(),
+                            ExternalLevel {
+                                rel_path: rel_path.clone(),
+                                handle: load_context.get_handle(path.clone()),
+                            },
+                        )
+                    })
+                    .collect(),
+            );
+            for (uid, path) in dependencies.iter() {
+                if let Some(tileset) = world.tilesets.get_mut(uid) {
+                    tileset.texture = Some(load_context.get_handle(path.clone()));
+                }
+            }
+
+            load_context.set_default_asset(
+                LoadedAsset::new(world).with_dependencies(
+                    dependencies
+                        .into_iter()
+                        .map(|x| x.1)
+                        .chain(external_levels.into_iter().map(|x| x.2))
+                        .collect(),
+                ),
+            );
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ldtk"]
+    }
+}
+
+/// Reads a project and its external levels straight from disk, without an asset server.
+///
+/// Tilesets get a placeholder texture if their image exists, so the project can still be
+/// validated.
+pub fn load_from_file(path: &Path) -> anyhow::Result<WorldProject> {
+    let mut project: ldtk_rust::Project = serde_json::from_slice(&std::fs::read(path)?)?;
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut external_levels = HashMap::new();
+    for level in project.levels.iter_mut() {
+        let rel_path = match &level.external_rel_path {
+            Some(rel_path) => rel_path.clone(),
+            None => continue,
+        };
+        *level = serde_json::from_slice(&std::fs::read(base_path.join(&rel_path))?)?;
+        external_levels.insert(
+            level.identifier.clone(),
+            ExternalLevel {
+                rel_path,
+                handle: Handle::default(),
+            },
+        );
+    }
+
+    let existing_tilesets: HashSet<i64> = project
+        .defs
+        .tilesets
+        .iter()
+        .filter(|tileset| {
+            tileset
+                .rel_path
+                .as_ref()
+                .map_or(false, |rel_path| base_path.join(rel_path).exists())
+        })
+        .map(|tileset| tileset.uid)
+        .collect();
+
+    let mut world = convert_project(project, external_levels);
+    for (uid, tileset) in world.tilesets.iter_mut() {
+        if existing_tilesets.contains(uid) {
+            tileset.texture = Some(Handle::default());
+        }
+    }
+
+    Ok(world)
+}
+
+/// Merges modified `.ldtkl` files into the projects that use them. Modifying the project will then
+/// trigger the usual tilemap reload.
+pub fn reload_external_levels(
+    mut level_events: EventReader<AssetEvent<LdtkLevel>>,
+    levels: Res<Assets<LdtkLevel>>,
+    mut maps: ResMut<Assets<WorldProject>>,
+) {
+    for handle in level_events.iter().filter_map(|event| match event {
+        AssetEvent::Modified { handle } => Some(handle),
+        _ => None,
+    }) {
+        let new_level = if let Some(level) = levels.get(handle) {
+            &level.level
+        } else {
+            continue;
+        };
+
+        let map_ids: Vec<_> = maps
+            .iter()
+            .filter(|(_, map)| match &map.source {
+                WorldSource::Ldtk(source) => source
+                    .external_levels
+                    .values()
+                    .any(|level| &level.handle == handle),
+                _ => false,
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        for map_id in map_ids {
+            let map = maps.get_mut(map_id).unwrap();
+            let defs = match &map.source {
+                WorldSource::Ldtk(source) => &source.defs,
+                _ => continue,
+            };
+            let new_level = convert_level(new_level, defs);
+            if let Some(level) = map
+                .levels
+                .iter_mut()
+                .find(|level| level.identifier == new_level.identifier)
+            {
+                info!("Reloading external level '{}'", new_level.identifier);
+                *level = new_level;
+            }
+        }
+    }
+}
+
+/// Replaces the layer data of a level in its JSON representation with that of the given level.
+fn patch_level(
+    json: &mut serde_json::Value,
+    level: &Level,
+    tilesets: &HashMap<i64, Tileset>,
+) -> anyhow::Result<()> {
+    let layers = json
+        .get_mut("layerInstances")
+        .and_then(|layers| layers.as_array_mut())
+        .ok_or_else(|| anyhow::anyhow!("Level '{}' has no layer data", level.identifier))?;
+
+    for layer in level.layers.iter().flatten() {
+        let layer_json = layers
+            .iter_mut()
+            .find(|json| json["__identifier"] == layer.identifier.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Layer '{}' not found", layer.identifier))?;
+
+        if let Some(int_grid) = &layer.int_grid {
+            layer_json["intGridCsv"] = serde_json::to_value(int_grid)?;
+        } else if layer_json["__type"] == "Tiles" {
+            let tileset = layer
+                .tileset_uid
+                .and_then(|uid| tilesets.get(&uid))
+                .ok_or_else(|| anyhow::anyhow!("Layer '{}' has no tileset", layer.identifier))?;
+            // Same as LDtk's `__cWid`
+            let stride = tileset.tile_size + tileset.spacing;
+            let tileset_width = (tileset.px_wid - 2 * tileset.padding + tileset.spacing) / stride;
+
+            layer_json["gridTiles"] = layer
+                .tiles
+                .iter()
+                .map(|tile| {
+                    let id = tile.id as i64;
+                    serde_json::json!({
+                        "px": tile.px,
+                        "src": [
+                            tileset.padding + id % tileset_width * stride,
+                            tileset.padding + id / tileset_width * stride,
+                        ],
+                        "f": tile.flip_x as i64 | (tile.flip_y as i64) << 1,
+                        "t": id,
+                        "d": [
+                            tile.px[0] / layer.grid_size + tile.px[1] / layer.grid_size * layer.c_wid,
+                        ],
+                    })
+                })
+                .collect();
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes JSON indented with tabs, like LDtk does.
+fn write_json(path: &Path, json: &serde_json::Value) -> anyhow::Result<()> {
+    let mut bytes = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
+    json.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut bytes, formatter,
+    ))?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Saves the layers of the given levels back to the LDtk project at `path`, or to their own level
+/// files if the project uses external levels.
+///
+/// Only layer data is replaced in the existing files, so anything else LDtk stores in them is kept
+/// as is.
+pub fn save_levels(world: &WorldProject, path: &Path, levels: &HashSet<i64>) -> anyhow::Result<()> {
+    let source = match &world.source {
+        WorldSource::Ldtk(source) => source,
+        _ => anyhow::bail!("Only LDtk worlds can be saved"),
+    };
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut project_json: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    let mut project_modified = false;
+
+    for level in world
+        .levels
+        .iter()
+        .filter(|level| levels.contains(&level.uid))
+    {
+        match source.external_levels.get(&level.identifier) {
+            Some(external_level) => {
+                let level_path = base_path.join(&external_level.rel_path);
+                let mut level_json = serde_json::from_slice(&std::fs::read(&level_path)?)?;
+                patch_level(&mut level_json, level, &world.tilesets)?;
+                write_json(&level_path, &level_json)?;
+            }
+            None => {
+                let level_json = project_json["levels"]
+                    .as_array_mut()
+                    .and_then(|levels| levels.iter_mut().find(|json| json["uid"] == level.uid))
+                    .ok_or_else(|| anyhow::anyhow!("Level '{}' not found", level.identifier))?;
+                patch_level(level_json, level, &world.tilesets)?;
+                project_modified = true;
+            }
+        }
+    }
+
+    if project_modified {
+        write_json(path, &project_json)?;
+    }
+
+    Ok(())
+}
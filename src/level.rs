@@ -0,0 +1,119 @@
+//! Format-agnostic description of the levels of a world.
+//!
+//! Loaders convert LDtk projects and Tiled maps to these types, so the rest of the game doesn't
+//! need to know which editor a world was made with. Coordinates follow the editors' conventions:
+//! they are in pixels, with +Y pointing down.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+/// Layer whose non-empty cells are solid.
+pub const COLLISION_LAYER: &str = "Collision";
+/// Layer whose non-empty cells are one-way platforms.
+pub const PLATFORM_LAYER: &str = "Platforms";
+/// Layer whose non-empty cells kill the player.
+pub const HAZARD_LAYER: &str = "Acid";
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub identifier: String,
+    /// Unique within its world.
+    pub uid: i64,
+    /// Position of the top left corner of the level in the world.
+    pub world_x: i64,
+    pub world_y: i64,
+    pub px_wid: i64,
+    pub px_hei: i64,
+    /// Background colour as a hex code, e.g. `34202b`.
+    pub bg_color: Option<String>,
+    /// Custom fields set on the level. Fields without a value are left out.
+    pub fields: HashMap<String, serde_json::Value>,
+    /// Tile and IntGrid layers, top-most first. `None` if the level data is missing, e.g. because
+    /// its external level file wasn't found.
+    pub layers: Option<Vec<LevelLayer>>,
+    pub entities: Vec<EntityInstance>,
+}
+
+impl Level {
+    /// Returns the value of a field of the level, if it has been set.
+    pub fn field(&self, identifier: &str) -> Option<&serde_json::Value> {
+        self.fields.get(identifier)
+    }
+
+    /// Returns the first layer with the given identifier.
+    pub fn layer(&self, identifier: &str) -> Option<&LevelLayer> {
+        self.layers
+            .iter()
+            .flatten()
+            .find(|layer| layer.identifier == identifier)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LevelLayer {
+    pub identifier: String,
+    pub grid_size: i64,
+    /// Size of the layer, in cells.
+    pub c_wid: i64,
+    pub c_hei: i64,
+    /// Offset of the layer from the top left corner of its level.
+    pub px_offset_x: i64,
+    pub px_offset_y: i64,
+    pub opacity: f32,
+    /// Parallax scrolling factor, see [`crate::world::LayerParallax`].
+    pub parallax_factor: Vec2,
+    /// Values of the cells of the layer, row by row, if it is an IntGrid layer. 0 means empty.
+    pub int_grid: Option<Vec<i64>>,
+    pub tileset_uid: Option<i64>,
+    pub tiles: Vec<TileInstance>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileInstance {
+    /// Position of the top left corner of the tile within its layer.
+    pub px: [i64; 2],
+    /// Index of the tile in its tileset, going left to right and then top to bottom.
+    pub id: u16,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Swaps the X and Y axes of the tile before flipping it, which Tiled uses to rotate tiles.
+    /// Always `false` in LDtk worlds.
+    pub flip_d: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntityInstance {
+    pub identifier: String,
+    /// Unique within its world. Entity reference fields contain it as `{"entityIid": iid}`.
+    pub iid: String,
+    /// Position of the pivot of the entity within its level.
+    pub px: [i64; 2],
+    pub width: i64,
+    pub height: i64,
+    /// Goes from (0, 0) for the top left corner of the entity to (1, 1) for the bottom right one.
+    pub pivot: Vec2,
+    /// Custom fields set on the entity. Fields without a value are left out.
+    pub fields: HashMap<String, serde_json::Value>,
+}
+
+impl EntityInstance {
+    /// Returns the value of a field of the entity, if it has been set.
+    pub fn field(&self, identifier: &str) -> Option<&serde_json::Value> {
+        self.fields.get(identifier)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub identifier: String,
+    pub tile_size: i64,
+    pub px_wid: i64,
+    pub px_hei: i64,
+    /// Pixels between two tiles of the image.
+    pub spacing: i64,
+    /// Pixels between the tiles and the edges of the image.
+    pub padding: i64,
+    /// `None` if the tileset has no image, e.g. LDtk's internal icons.
+    pub texture: Option<Handle<Image>>,
+}
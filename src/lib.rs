@@ -1,21 +1,46 @@
 #![allow(clippy::too_many_arguments)]
 
+pub mod animation;
+pub mod background;
+pub mod checkpoints;
 pub mod debug;
+pub mod doors;
+pub mod editor;
+pub mod environment;
+pub mod exploration;
+pub mod export;
 pub mod follow;
+pub mod generator;
 pub mod input_mapper;
+pub mod ldtk;
+pub mod level;
+pub mod map;
 pub mod pausing;
 pub mod physics;
 pub mod player;
+pub mod player_animation;
+pub mod player_presets;
+pub mod tile_animation;
+pub mod tiled;
 pub mod time;
+pub mod transition;
 pub mod util;
+pub mod validation;
+pub mod warp;
 pub mod world;
+pub mod worlds;
 
+use background::BackgroundSettings;
 use debug::DebugMode;
 use input_mapper::InputMapper;
 
 pub use player::{Player, PlayerProperties};
 use world::GameWorld;
-pub use world::LdtkProject;
+pub use world::WorldProject;
+use worlds::WorldRegistry;
+
+/// Path of the world the game starts in, relative to the assets folder.
+pub const STARTING_WORLD: &str = "world.ldtk";
 
 use bevy::{prelude::*, render::camera::ScalingMode};
 
@@ -26,6 +51,8 @@ pub enum AppState {
     Loading,
     Playing,
     Paused,
+    /// The world failed to load. See [`validation::WorldErrors`].
+    Error,
 }
 
 pub fn camera_follow_player(
@@ -60,6 +87,8 @@ pub fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut input_mapper: ResMut<InputMapper>,
+    mut background_settings: ResMut<BackgroundSettings>,
+    mut world_registry: ResMut<WorldRegistry>,
 ) {
     commands.spawn_bundle(OrthographicCameraBundle {
         orthographic_projection: OrthographicProjection {
@@ -73,8 +102,10 @@ pub fn setup(
         ..OrthographicCameraBundle::new_2d()
     });
 
-    let ldtk: Handle<LdtkProject> = asset_server.load("world.ldtk");
-    commands.insert_resource(GameWorld { ldtk });
+    let project = world_registry.load(&asset_server, STARTING_WORLD);
+    world_registry.current = Some(STARTING_WORLD.to_owned());
+    commands.insert_resource(GameWorld { project });
 
     input_mapper.mappings = asset_server.load::<InputMappings, _>("input.ron");
+    background_settings.backgrounds = asset_server.load("backgrounds.bg.ron");
 }
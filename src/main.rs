@@ -7,16 +7,31 @@ use bevy::{
 };
 
 use platformer_test::{
+    animation::AnimationPlugin,
+    background::BackgroundPlugin,
     camera_follow_player,
+    checkpoints::CheckpointPlugin,
     debug::DebugPlugin,
+    doors::DoorPlugin,
+    editor::EditorPlugin,
+    environment::EnvironmentPlugin,
+    exploration::ExplorationPlugin,
     follow::FollowPlugin,
+    generator::GeneratorPlugin,
     input_mapper::InputBindingPlugin,
+    map::MapPlugin,
     pausing::PausePlugin,
     physics::PhysicsPlugin,
     player::{spawn_player, PlayerPlugin},
+    player_animation::PlayerAnimationPlugin,
+    player_presets::PlayerPresetsPlugin,
     setup, show_fps,
     time::TimePlugin,
-    world::{change_to_playing_state_on_level_load, WorldPlugin},
+    transition::RoomTransitionPlugin,
+    validation::{show_world_errors, validate_loaded_world, WorldErrors},
+    warp::WarpPlugin,
+    world::WorldPlugin,
+    worlds::WorldsPlugin,
     AppState,
 };
 
@@ -37,11 +52,23 @@ pub fn main() {
     .add_plugin(bevy_egui::EguiPlugin)
     .add_plugin(InputBindingPlugin)
     .add_plugin(WorldPlugin)
+    .add_plugin(WorldsPlugin)
+    .add_plugin(GeneratorPlugin)
     .add_plugin(PlayerPlugin)
+    .add_plugin(AnimationPlugin)
+    .add_plugin(PlayerAnimationPlugin)
+    .add_plugin(PlayerPresetsPlugin)
     .add_plugin(PhysicsPlugin)
     .add_plugin(FollowPlugin)
     .add_plugin(TimePlugin)
-    .insert_resource(ClearColor(Color::hex("34202b").unwrap()))
+    .add_plugin(BackgroundPlugin)
+    .add_plugin(ExplorationPlugin)
+    .add_plugin(MapPlugin)
+    .add_plugin(RoomTransitionPlugin)
+    .add_plugin(EnvironmentPlugin)
+    .add_plugin(DoorPlugin)
+    .add_plugin(WarpPlugin)
+    .add_plugin(CheckpointPlugin)
     .insert_resource(WindowDescriptor {
         title: "Platform Template".to_owned(),
         present_mode: PresentMode::Mailbox,
@@ -50,12 +77,13 @@ pub fn main() {
     .add_startup_system(setup)
     .add_system(camera_follow_player)
     .add_system(show_fps)
-    .add_system_set(
-        SystemSet::on_update(AppState::Loading).with_system(change_to_playing_state_on_level_load),
-    )
+    .init_resource::<WorldErrors>()
+    .add_system(validate_loaded_world)
+    .add_system_set(SystemSet::on_update(AppState::Error).with_system(show_world_errors))
     .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(spawn_player))
     .add_system(exit_on_window_close_system)
     .add_plugin(DebugPlugin)
+    .add_plugin(EditorPlugin)
     .add_plugin(PausePlugin);
 
     app.run();
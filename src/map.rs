@@ -0,0 +1,338 @@
+//! Minimap shown while playing and full map shown in the pause menu.
+//!
+//! Both are generated from the layout of the levels in the world and their collision grids. Only
+//! the parts of the world recorded in [`ExploredRooms`] are shown.
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::{
+    exploration::ExploredRooms,
+    player::{CurrentRoom, RoomPos},
+    world::{world_changed, GameWorld, LevelTile, TileRect, TILE_SIZE},
+    worlds::WorldRegistry,
+    AppState, Player, WorldProject,
+};
+
+const MINIMAP_SIZE: egui::Vec2 = egui::vec2(160., 100.);
+const MINIMAP_TILE_SIZE: f32 = 1.5;
+const FULL_MAP_DEFAULT_TILE_SIZE: f32 = 4.;
+const FULL_MAP_TILE_SIZE_RANGE: std::ops::RangeInclusive<f32> = 1.0..=16.;
+
+const ROOM_COLOR: egui::Color32 = egui::Color32::from_rgb(0x34, 0x20, 0x2b);
+const SOLID_COLOR: egui::Color32 = egui::Color32::from_rgb(0x9a, 0x8f, 0xa8);
+const ROOM_OUTLINE_COLOR: egui::Color32 = egui::Color32::from_rgb(0x69, 0x6a, 0x79);
+const CURRENT_ROOM_OUTLINE_COLOR: egui::Color32 = egui::Color32::from_rgb(0xe2, 0xc4, 0x4a);
+const PLAYER_COLOR: egui::Color32 = egui::Color32::from_rgb(0x4a, 0xe2, 0x6a);
+const FOG_COLOR: egui::Color32 = egui::Color32::from_rgb(0x1a, 0x10, 0x16);
+
+pub struct MapRoom {
+    pub identifier: String,
+    pub rect: TileRect,
+    /// Whether each tile of the room is solid, row by row.
+    pub solid: Vec<bool>,
+}
+
+impl MapRoom {
+    pub fn is_solid(&self, x: i64, y: i64) -> bool {
+        self.rect.contains(x, y)
+            && self.solid
+                [((y - self.rect.min_y) * self.rect.width() + (x - self.rect.min_x)) as usize]
+    }
+}
+
+/// The rooms of the current world, used to draw maps.
+#[derive(Default)]
+pub struct MapLayout {
+    /// Path of the world the rooms belong to.
+    pub world: String,
+    pub rooms: Vec<MapRoom>,
+    /// Size of the [`RoomPos`] cells in tiles.
+    pub cell_size: Option<(i64, i64)>,
+}
+
+impl MapLayout {
+    pub fn from_project(path: &str, project: &WorldProject) -> Self {
+        Self {
+            world: path.to_owned(),
+            cell_size: project
+                .world_grid
+                .map(|(width, height)| (width / TILE_SIZE as i64, height / TILE_SIZE as i64)),
+            rooms: project
+                .levels
+                .iter()
+                .map(|level| {
+                    let rect = TileRect::of_level(level);
+                    MapRoom {
+                        identifier: level.identifier.clone(),
+                        rect,
+                        solid: (rect.min_y..rect.max_y)
+                            .flat_map(|y| {
+                                // get_tile takes bevy coordinates, which are +Y up
+                                (rect.min_x..rect.max_x)
+                                    .map(move |x| project.get_tile(x, -y) == LevelTile::SOLID)
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Pan and zoom of the full map.
+pub struct FullMapView {
+    /// Offset from the player position, in screen points.
+    pub offset: egui::Vec2,
+    pub tile_size: f32,
+}
+
+impl Default for FullMapView {
+    fn default() -> Self {
+        Self {
+            offset: egui::Vec2::ZERO,
+            tile_size: FULL_MAP_DEFAULT_TILE_SIZE,
+        }
+    }
+}
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapLayout>()
+            .init_resource::<FullMapView>()
+            .add_system(build_map_layout)
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(show_minimap))
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(reset_full_map_view))
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(show_full_map));
+    }
+}
+
+fn build_map_layout(
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    maps: Res<Assets<WorldProject>>,
+    world: Res<GameWorld>,
+    registry: Res<WorldRegistry>,
+    mut layout: ResMut<MapLayout>,
+) {
+    if !world_changed(&world, &mut map_events) {
+        return;
+    }
+
+    if let Some(map) = maps.get(&world.project) {
+        let path = registry.current.as_deref().unwrap_or_default();
+        *layout = MapLayout::from_project(path, map);
+    }
+}
+
+/// Returns the area covered by a [`RoomPos`] cell, in LDtk tile coordinates.
+fn cell_rect((x, y): (i64, i64), (width, height): (i64, i64)) -> TileRect {
+    // RoomPos is +Y up, LDtk coordinates are +Y down
+    TileRect {
+        min_x: x * width,
+        min_y: -(y + 1) * height + 1,
+        max_x: (x + 1) * width,
+        max_y: -y * height + 1,
+    }
+}
+
+/// Returns the [`RoomPos`] cells overlapping an area in LDtk tile coordinates.
+fn cells_in(rect: TileRect, (width, height): (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+    let (min_x, max_x) = (
+        rect.min_x.div_euclid(width),
+        (rect.max_x - 1).div_euclid(width),
+    );
+    let (min_y, max_y) = (
+        (-(rect.max_y - 1)).div_euclid(height),
+        (-rect.min_y).div_euclid(height),
+    );
+    (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+}
+
+/// Draws the explored rooms in the layout, centered around `center`. Positions are in LDtk tile
+/// coordinates.
+fn draw_rooms(
+    painter: &egui::Painter,
+    layout: &MapLayout,
+    explored: &ExploredRooms,
+    center: egui::Pos2,
+    tile_size: f32,
+    current_room: Option<&CurrentRoom>,
+    player_position: egui::Pos2,
+) {
+    let clip_rect = painter.clip_rect();
+    let origin = clip_rect.center() - center.to_vec2() * tile_size;
+    let to_screen = |x: i64, y: i64| origin + egui::vec2(x as f32, y as f32) * tile_size;
+
+    for room in layout.rooms.iter() {
+        let room_rect = egui::Rect::from_min_max(
+            to_screen(room.rect.min_x, room.rect.min_y),
+            to_screen(room.rect.max_x, room.rect.max_y),
+        );
+        if !clip_rect.intersects(room_rect)
+            || !explored.is_level_explored(&layout.world, &room.identifier)
+        {
+            continue;
+        }
+
+        painter.rect_filled(room_rect, 0., ROOM_COLOR);
+
+        // Draw horizontal runs of solid tiles as a single rect to keep the shape count low
+        for y in room.rect.min_y..room.rect.max_y {
+            let mut run_start = None;
+            for x in room.rect.min_x..=room.rect.max_x {
+                match (run_start, room.is_solid(x, y)) {
+                    (None, true) => run_start = Some(x),
+                    (Some(start), false) => {
+                        painter.rect_filled(
+                            egui::Rect::from_min_max(to_screen(start, y), to_screen(x, y + 1)),
+                            0.,
+                            SOLID_COLOR,
+                        );
+                        run_start = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // Hide the parts of the room that haven't been visited yet
+        if let Some(cell_size) = layout.cell_size {
+            for (x, y) in cells_in(room.rect, cell_size) {
+                if explored.is_cell_explored(&layout.world, &room.identifier, &RoomPos { x, y }) {
+                    continue;
+                }
+                let cell = cell_rect((x, y), cell_size);
+                let fog_rect = egui::Rect::from_min_max(
+                    to_screen(cell.min_x, cell.min_y),
+                    to_screen(cell.max_x, cell.max_y),
+                )
+                .intersect(room_rect);
+                painter.rect_filled(fog_rect, 0., FOG_COLOR);
+            }
+        }
+    }
+
+    for room in layout
+        .rooms
+        .iter()
+        .filter(|room| explored.is_level_explored(&layout.world, &room.identifier))
+    {
+        let is_current = current_room.map_or(false, |current_room| {
+            current_room.world == layout.world && current_room.id == room.identifier
+        });
+        painter.rect_stroke(
+            egui::Rect::from_min_max(
+                to_screen(room.rect.min_x, room.rect.min_y),
+                to_screen(room.rect.max_x, room.rect.max_y),
+            ),
+            0.,
+            egui::Stroke::new(
+                if is_current { 2. } else { 1. },
+                if is_current {
+                    CURRENT_ROOM_OUTLINE_COLOR
+                } else {
+                    ROOM_OUTLINE_COLOR
+                },
+            ),
+        );
+    }
+
+    painter.circle_filled(
+        origin + player_position.to_vec2() * tile_size,
+        (tile_size * 0.75).max(2.),
+        PLAYER_COLOR,
+    );
+}
+
+/// Returns the player position in LDtk tile coordinates (+Y down) and the room it is in.
+fn player_location<'a>(
+    player: &'a Query<(&GlobalTransform, Option<&CurrentRoom>), With<Player>>,
+) -> Option<(egui::Pos2, Option<&'a CurrentRoom>)> {
+    player.get_single().ok().map(|(transform, current_room)| {
+        (
+            egui::pos2(
+                transform.translation.x + 0.5,
+                -transform.translation.y + 0.5,
+            ),
+            current_room,
+        )
+    })
+}
+
+fn show_minimap(
+    mut egui: ResMut<bevy_egui::EguiContext>,
+    layout: Res<MapLayout>,
+    explored: Res<ExploredRooms>,
+    player: Query<(&GlobalTransform, Option<&CurrentRoom>), With<Player>>,
+) {
+    let (center, current_room) = if let Some(location) = player_location(&player) {
+        location
+    } else {
+        return;
+    };
+
+    egui::Area::new("minimap")
+        .anchor(egui::Align2::RIGHT_TOP, [-8., 8.])
+        .show(egui.ctx_mut(), |ui| {
+            egui::Frame::dark_canvas(ui.style()).show(ui, |ui| {
+                let (_, painter) = ui.allocate_painter(MINIMAP_SIZE, egui::Sense::hover());
+                draw_rooms(
+                    &painter,
+                    &layout,
+                    &explored,
+                    center,
+                    MINIMAP_TILE_SIZE,
+                    current_room,
+                    center,
+                );
+            });
+        });
+}
+
+fn reset_full_map_view(mut view: ResMut<FullMapView>) {
+    *view = default();
+}
+
+fn show_full_map(
+    mut egui: ResMut<bevy_egui::EguiContext>,
+    layout: Res<MapLayout>,
+    explored: Res<ExploredRooms>,
+    mut view: ResMut<FullMapView>,
+    player: Query<(&GlobalTransform, Option<&CurrentRoom>), With<Player>>,
+) {
+    let (player_position, current_room) = if let Some(location) = player_location(&player) {
+        location
+    } else {
+        return;
+    };
+
+    egui::CentralPanel::default().show(egui.ctx_mut(), |ui| {
+        ui.heading("Map");
+        ui.label("Drag to pan, scroll to zoom");
+        let (response, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::drag());
+
+        view.offset += response.drag_delta();
+        if response.hovered() {
+            let scroll = ui.input().scroll_delta.y;
+            if scroll != 0. {
+                view.tile_size = (view.tile_size * (1. + scroll * 0.002)).clamp(
+                    *FULL_MAP_TILE_SIZE_RANGE.start(),
+                    *FULL_MAP_TILE_SIZE_RANGE.end(),
+                );
+            }
+        }
+
+        let center = player_position - view.offset / view.tile_size;
+        draw_rooms(
+            &painter,
+            &layout,
+            &explored,
+            center,
+            view.tile_size,
+            current_room,
+            player_position,
+        );
+    });
+}
@@ -15,7 +15,7 @@ use iyes_loopless::prelude::*;
 use crate::AppState;
 use crate::{
     world::{GameWorld, LevelTile},
-    LdtkProject,
+    WorldProject,
 };
 
 #[derive(Component, Deref, DerefMut, Default)]
@@ -26,6 +26,16 @@ pub struct RectCollision {
     pub rect: Rect,
 }
 
+impl RectCollision {
+    /// Collider for a body of the given size centered on its transform.
+    pub fn centered(size: Vec2) -> Self {
+        Self {
+            // Colliders are offset by (0.5, 0.5) from sprites, see `tiles_to_check`
+            rect: Rect::from_min_size(vec2(0.5, 0.5) - size / 2., size),
+        }
+    }
+}
+
 pub trait RectExtras: Sized {
     /// left-top corner plus a size (stretching right-down).
     fn from_min_size(min: Vec2, size: Vec2) -> Self;
@@ -120,30 +130,43 @@ pub struct SensedBodies {
 
 const PHYSICS_TIME_STEP: Duration = Duration::from_millis(16);
 
+/// While set, bodies stay in place and sensors keep their last readings. Used to stop the action
+/// during room transitions.
+#[derive(Default)]
+pub struct PhysicsFrozen(pub bool);
+
+fn physics_running(frozen: Res<PhysicsFrozen>) -> bool {
+    !frozen.0
+}
+
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<PhysicsWorld>().add_stage_before(
-            CoreStage::PostUpdate,
-            "physics",
-            FixedTimestepStage::new(PHYSICS_TIME_STEP).with_stage(
-                SystemStage::parallel().with_system_set(
-                    SystemSet::new()
-                        .with_system(update_physics_world.run_in_bevy_state(AppState::Playing))
-                        .with_system(
-                            move_bodies
-                                .run_in_bevy_state(AppState::Playing)
-                                .after(update_physics_world),
-                        )
-                        .with_system(
-                            detect_bodies
-                                .run_in_bevy_state(AppState::Playing)
-                                .after(update_physics_world),
-                        ),
+        app.init_resource::<PhysicsWorld>()
+            .init_resource::<PhysicsFrozen>()
+            .add_stage_before(
+                CoreStage::PostUpdate,
+                "physics",
+                FixedTimestepStage::new(PHYSICS_TIME_STEP).with_stage(
+                    SystemStage::parallel().with_system_set(
+                        SystemSet::new()
+                            .with_system(update_physics_world.run_in_bevy_state(AppState::Playing))
+                            .with_system(
+                                move_bodies
+                                    .run_in_bevy_state(AppState::Playing)
+                                    .run_if(physics_running)
+                                    .after(update_physics_world),
+                            )
+                            .with_system(
+                                detect_bodies
+                                    .run_in_bevy_state(AppState::Playing)
+                                    .run_if(physics_running)
+                                    .after(update_physics_world),
+                            ),
+                    ),
                 ),
-            ),
-        );
+            );
     }
 }
 
@@ -171,16 +194,17 @@ fn world_to_tile_pos(pos: Vec2) -> (i64, i64) {
     (pos.x.floor() as i64, pos.y.floor() as i64)
 }
 
-fn is_colliding_with_world_masked(rect: Rect, project: &LdtkProject, mask: LevelTile) -> bool {
+fn is_colliding_with_world_masked(rect: Rect, project: &WorldProject, mask: LevelTile) -> bool {
     tiles_to_check(rect)
         .into_iter()
         .any(|(x, y)| !(mask & project.get_tile(x, y)).is_empty())
 }
 
+/// `original_bottom` is the bottom of the collider before the body moved.
 fn is_colliding_with_world(
     rect: Rect,
-    project: &LdtkProject,
-    original_position: Vec2,
+    project: &WorldProject,
+    original_bottom: f32,
     collide_with_platforms: bool,
 ) -> bool {
     tiles_to_check(rect).into_iter().any(|(x, y)| {
@@ -189,8 +213,7 @@ fn is_colliding_with_world(
         match tile {
             LevelTile::PLATFORM => {
                 // Only if the body was on top of the platform
-                let original_bottom_y = original_position.y - rect.height() + 0.5;
-                collide_with_platforms && original_bottom_y > y as f32
+                collide_with_platforms && original_bottom - 0.5 > y as f32
             }
 
             LevelTile::SOLID => true,
@@ -200,20 +223,16 @@ fn is_colliding_with_world(
     })
 }
 
+/// Returns every tile touched by the rect, whatever its size.
 fn tiles_to_check(rect: Rect) -> Vec<(i64, i64)> {
-    // FIXME: This assumes tile size ~= collider size and only checks corners
-    let x = rect.min.x;
-    let y = rect.min.y;
-    let w = rect.width();
-    let h = rect.height();
     // Sprites are centered, we offset by (-0.5, +0.5) to correct the position
     // FIXME: Maybe this should be somewhere else?
-    vec![
-        world_to_tile_pos(vec2(x - 0.5, y + 0.5)),
-        world_to_tile_pos(vec2(x + w - 0.5, y + 0.5)),
-        world_to_tile_pos(vec2(x - 0.5, y + h + 0.5)),
-        world_to_tile_pos(vec2(x + w - 0.5, y + h + 0.5)),
-    ]
+    let offset = vec2(-0.5, 0.5);
+    let (min_x, min_y) = world_to_tile_pos(rect.min + offset);
+    let (max_x, max_y) = world_to_tile_pos(rect.max + offset);
+    (min_x..=max_x)
+        .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+        .collect()
 }
 
 fn update_physics_world(
@@ -231,14 +250,14 @@ fn update_physics_world(
 pub fn detect_bodies(
     mut commands: Commands,
     world: Res<GameWorld>,
-    map_assets: Res<Assets<LdtkProject>>,
+    map_assets: Res<Assets<WorldProject>>,
 
     // Rect colliders
     rect_colliders: Query<(Entity, &RectCollision, &GlobalTransform), With<StaticBody>>,
     // Bodies
     bodies: Query<(Entity, &GlobalTransform, &RectCollision, &SensorBody)>,
 ) {
-    let project = if let Some(project) = map_assets.get(&world.ldtk) {
+    let project = if let Some(project) = map_assets.get(&world.project) {
         project
     } else {
         return;
@@ -274,7 +293,7 @@ fn move_bodies(
     mut commands: Commands,
     world: Res<GameWorld>,
     physics_world: Res<PhysicsWorld>,
-    map_assets: Res<Assets<LdtkProject>>,
+    map_assets: Res<Assets<WorldProject>>,
     mut bodies: Query<(
         Entity,
         &mut Transform,
@@ -284,14 +303,14 @@ fn move_bodies(
     )>,
 ) {
     let delta_time = PHYSICS_TIME_STEP.as_secs_f32();
-    let project = if let Some(x) = map_assets.get(&world.ldtk) {
+    let project = if let Some(x) = map_assets.get(&world.project) {
         x
     } else {
         return;
     };
 
     for (entity, mut transform, mut velocity, collision, body) in bodies.iter_mut() {
-        let original_position = transform.translation.truncate();
+        let original_bottom = transform.translation.y + collision.rect.min.y;
 
         let is_colliding = |position: Vec2| {
             let rect = collision.rect.translate(position);
@@ -299,7 +318,7 @@ fn move_bodies(
                 || is_colliding_with_world(
                     rect,
                     project,
-                    original_position,
+                    original_bottom,
                     !body.pass_through_platforms,
                 )
         };
@@ -1,20 +1,27 @@
 use std::time::Duration;
 
 use crate::{
+    animation::Animator,
     debug::DebugMode,
+    environment::LevelEnvironment,
     follow::CameraFollow,
     input_mapper::{self, Input},
+    level::Level,
     physics::{
-        CollisionSide, KinematicBody, KinematicCollisions, RectCollision, RectExtras, SensedBodies,
-        SensorBody, Velocity,
+        CollisionSide, KinematicBody, KinematicCollisions, PhysicsFrozen, RectCollision,
+        RectExtras, SensedBodies, SensorBody, Velocity,
     },
+    player_animation::{PlayerAnimation, PlayerAnimations},
     time::GameplayTime,
+    validation::START_POINT_IDENTIFIER,
     world::{GameWorld, TILE_SIZE},
-    AppState, LdtkProject,
+    worlds::WorldRegistry,
+    AppState, WorldProject,
 };
-use bevy::math::vec2;
+use bevy::math::{vec2, vec3};
 use bevy::{prelude::*, sprite::Rect};
 use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
 
 pub struct PlayerPlugin;
 
@@ -25,18 +32,28 @@ impl Plugin for PlayerPlugin {
                 .with_system(set_player_state.before(update_player))
                 .with_system(update_jump.before(update_player))
                 .with_system(update_player)
-                .with_system(update_camera_bounds),
+                .with_system(update_camera_bounds)
+                .with_system(teleport_player.after(update_camera_bounds)),
         )
-        .add_system(update_current_room)
+        .add_event::<RoomEntered>()
+        .add_event::<RoomExited>()
+        .add_event::<TeleportPlayer>()
+        .add_system(update_current_room.before(teleport_player))
         .add_system(update_room_pos)
         .add_system(debug_player_state);
     }
 }
 
-#[derive(Debug)]
+/// Movement tuning of the player, loaded from the presets in `assets/player.props.ron`. Durations
+/// are in milliseconds there.
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PlayerProperties {
     pub max_run_speed: f32,
     pub terminal_speed: f32,
+    /// Whether holding down on the ground crouches.
+    pub can_crouch: bool,
+    /// Maximum speed while crouching.
+    pub crawl_speed: f32,
     pub ground_acceleration: f32,
     pub ground_decceleration: f32,
     pub ground_direction_change_acceleration: f32,
@@ -46,7 +63,9 @@ pub struct PlayerProperties {
     pub gravity: f32,
     pub jump_force: f32,
     pub jump_gravity: f32,
+    #[serde(with = "crate::util::duration_ms")]
     pub coyote_time: Duration,
+    #[serde(with = "crate::util::duration_ms")]
     pub jump_buffer_time: Duration,
     pub jumps_available: u32,
     pub multijump_coefficient: f32,
@@ -54,13 +73,57 @@ pub struct PlayerProperties {
     pub can_walljump: bool,
     pub walljump_vertical_force: f32,
     pub walljump_horizontal_force: f32,
+    #[serde(with = "crate::util::duration_ms")]
     pub dead_time_after_walljump: Duration,
+    /// Whether the player clings to walls when touching them instead of sliding down right away.
+    pub can_wall_stick: bool,
+    /// How long the player clings to a wall before sliding.
+    #[serde(with = "crate::util::duration_ms")]
+    pub wall_grip_time: Duration,
+    /// How much longer the player can keep clinging by holding toward the wall. Restored when
+    /// landing.
+    #[serde(with = "crate::util::duration_ms")]
+    pub wall_stick_stamina: Duration,
+    pub can_dash: bool,
+    pub dash_directions: DashDirections,
+    pub dash_speed: f32,
+    #[serde(with = "crate::util::duration_ms")]
+    pub dash_duration: Duration,
+    /// Time after a dash ends before the player can dash again.
+    #[serde(with = "crate::util::duration_ms")]
+    pub dash_cooldown: Duration,
+    /// Multiplier of the gravity applied while dashing. 0 dashes in a straight line.
+    pub dash_gravity_scale: f32,
+    /// Dashes that can be done before landing again.
+    pub air_dashes: u32,
+    /// Whether the player grabs ledges that are beside their head while falling.
+    pub can_ledge_grab: bool,
+    /// Whether the player climbs up ledges on their own after hanging for `ledge_climb_delay`.
+    pub ledge_auto_climb: bool,
+    #[serde(with = "crate::util::duration_ms")]
+    pub ledge_climb_delay: Duration,
+    /// Whether holding up climbs ledges.
+    pub ledge_climb_on_input: bool,
+    /// Whether holding down lets go of ledges.
+    pub can_ledge_drop: bool,
+    /// Whether jumping from a ledge is allowed.
+    pub can_ledge_jump: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DashDirections {
+    /// Dashes left or right only.
+    Horizontal,
+    /// Dashes in any of the eight directions held, diagonals included.
+    Eight,
 }
 
 impl Default for PlayerProperties {
     fn default() -> Self {
         Self {
             max_run_speed: 12.,
+            can_crouch: true,
+            crawl_speed: 4.,
             ground_acceleration: 85.,
             ground_decceleration: 65.,
             ground_direction_change_acceleration: 85. + 40.,
@@ -80,103 +143,195 @@ impl Default for PlayerProperties {
             walljump_vertical_force: 20.,
             walljump_horizontal_force: 10.,
             dead_time_after_walljump: Duration::from_millis(200),
+            can_wall_stick: true,
+            wall_grip_time: Duration::from_millis(250),
+            wall_stick_stamina: Duration::from_millis(1500),
+            can_dash: true,
+            dash_directions: DashDirections::Eight,
+            dash_speed: 30.,
+            dash_duration: Duration::from_millis(150),
+            dash_cooldown: Duration::from_millis(250),
+            dash_gravity_scale: 0.,
+            air_dashes: 1,
+            can_ledge_grab: true,
+            ledge_auto_climb: false,
+            ledge_climb_delay: Duration::from_millis(300),
+            ledge_climb_on_input: true,
+            can_ledge_drop: true,
+            can_ledge_jump: true,
         }
     }
 }
 
 impl PlayerProperties {
-    pub fn show_ui(&mut self, egui_ctx: &egui::Context) {
-        egui::CentralPanel::default().show(egui_ctx, |ui| {
-            ui.add(egui::Slider::new(&mut self.max_run_speed, 0f32..=100.).text("Max run speed"));
-            egui::CollapsingHeader::new("Grounded properties")
-                .default_open(true)
-                .show(ui, |ui| {
-                    ui.add(
-                        egui::Slider::new(&mut self.ground_decceleration, 0f32..=100.)
-                            .text("Ground decceleration"),
-                    );
-                    ui.add(
-                        egui::Slider::new(&mut self.ground_acceleration, 0f32..=100.)
-                            .text("Ground acceleration"),
-                    );
-                    ui.add(
-                        egui::Slider::new(
-                            &mut self.ground_direction_change_acceleration,
-                            0f32..=200.,
-                        )
+    pub fn show_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.max_run_speed, 0f32..=100.).text("Max run speed"));
+        egui::CollapsingHeader::new("Grounded properties")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut self.ground_decceleration, 0f32..=100.)
+                        .text("Ground decceleration"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.ground_acceleration, 0f32..=100.)
+                        .text("Ground acceleration"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.ground_direction_change_acceleration, 0f32..=200.)
                         .text("Ground direction change acceleration"),
-                    );
-                });
-            egui::CollapsingHeader::new("Airborne properties")
-                .default_open(true)
-                .show(ui, |ui| {
-                    ui.add(
-                        egui::Slider::new(&mut self.air_acceleration, 0f32..=100.)
-                            .text("Air acceleration"),
-                    );
+                );
+                ui.add(egui::Checkbox::new(&mut self.can_crouch, "Allow crouching"));
+                if self.can_crouch {
                     ui.add(
-                        egui::Slider::new(&mut self.air_decceleration, 0f32..=100.)
-                            .text("Air decceleration"),
+                        egui::Slider::new(&mut self.crawl_speed, 0f32..=100.).text("Crawl speed"),
                     );
-                    ui.add(
-                        egui::Slider::new(&mut self.air_direction_change_acceleration, 0f32..=200.)
-                            .text("Air direction change acceleration"),
-                    );
-                    ui.add(egui::Slider::new(&mut self.gravity, 0f32..=200.).text("Gravity"));
+                }
+            });
+        egui::CollapsingHeader::new("Airborne properties")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut self.air_acceleration, 0f32..=100.)
+                        .text("Air acceleration"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.air_decceleration, 0f32..=100.)
+                        .text("Air decceleration"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.air_direction_change_acceleration, 0f32..=200.)
+                        .text("Air direction change acceleration"),
+                );
+                ui.add(egui::Slider::new(&mut self.gravity, 0f32..=200.).text("Gravity"));
 
+                ui.add(
+                    egui::Slider::new(&mut self.terminal_speed, 0f32..=100.).text("Terminal speed"),
+                );
+            });
+        egui::CollapsingHeader::new("Jump properties")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add(egui::Slider::new(&mut self.jump_force, 1f32..=100.).text("Jump Force"));
+                ui.add(egui::Slider::new(&mut self.jump_gravity, 0f32..=100.).text("Jump Gravity"));
+                ui.add(egui::Slider::new(&mut self.jumps_available, 0..=3).text("Jumps Available"));
+                ui.add(
+                    egui::Slider::new(&mut self.multijump_coefficient, 0f32..=1.)
+                        .text("Multijump coefficient"),
+                );
+                let mut allow_wallsliding = self.wallslide_max_v_speed.is_some();
+                ui.add(egui::Checkbox::new(
+                    &mut allow_wallsliding,
+                    "Allow wallsliding",
+                ));
+                if !allow_wallsliding {
+                    self.wallslide_max_v_speed = None;
+                } else {
+                    let mut wallslide_max_v_speed = self.wallslide_max_v_speed.unwrap_or(15.);
                     ui.add(
-                        egui::Slider::new(&mut self.terminal_speed, 0f32..=100.)
-                            .text("Terminal speed"),
+                        egui::Slider::new(&mut wallslide_max_v_speed, 0f32..=100.)
+                            .text("Wallslide max vertical speed"),
                     );
-                });
-            egui::CollapsingHeader::new("Jump properties")
-                .default_open(true)
-                .show(ui, |ui| {
-                    ui.add(egui::Slider::new(&mut self.jump_force, 1f32..=100.).text("Jump Force"));
+                    self.wallslide_max_v_speed = Some(wallslide_max_v_speed);
+                }
+                ui.add(egui::Checkbox::new(
+                    &mut self.can_walljump,
+                    "Allow walljumps",
+                ));
+                if self.can_walljump {
                     ui.add(
-                        egui::Slider::new(&mut self.jump_gravity, 0f32..=100.).text("Jump Gravity"),
+                        egui::Slider::new(&mut self.walljump_horizontal_force, 0f32..=100.)
+                            .text("Walljump horizontal force"),
                     );
                     ui.add(
-                        egui::Slider::new(&mut self.jumps_available, 0..=3).text("Jumps Available"),
+                        egui::Slider::new(&mut self.walljump_vertical_force, 0f32..=100.)
+                            .text("Walljump vertical force"),
+                    );
+                }
+                ui.add(egui::Checkbox::new(
+                    &mut self.can_wall_stick,
+                    "Allow wall sticking",
+                ));
+                if self.can_wall_stick {
+                    duration_slider(ui, &mut self.wall_grip_time, 2000, "Wall grip time (ms)");
+                    duration_slider(
+                        ui,
+                        &mut self.wall_stick_stamina,
+                        5000,
+                        "Wall stick stamina (ms)",
                     );
+                }
+            });
+        egui::CollapsingHeader::new("Dash properties")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add(egui::Checkbox::new(&mut self.can_dash, "Allow dashing"));
+                if self.can_dash {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut self.dash_directions,
+                            DashDirections::Horizontal,
+                            "Horizontal",
+                        );
+                        ui.radio_value(
+                            &mut self.dash_directions,
+                            DashDirections::Eight,
+                            "Eight directions",
+                        );
+                    });
+                    ui.add(egui::Slider::new(&mut self.dash_speed, 0f32..=100.).text("Dash speed"));
+                    duration_slider(ui, &mut self.dash_duration, 1000, "Dash duration (ms)");
+                    duration_slider(ui, &mut self.dash_cooldown, 2000, "Dash cooldown (ms)");
                     ui.add(
-                        egui::Slider::new(&mut self.multijump_coefficient, 0f32..=1.)
-                            .text("Multijump coefficient"),
+                        egui::Slider::new(&mut self.dash_gravity_scale, 0f32..=1.)
+                            .text("Dash gravity multiplier"),
                     );
-                    let mut allow_wallsliding = self.wallslide_max_v_speed.is_some();
+                    ui.add(egui::Slider::new(&mut self.air_dashes, 0..=3).text("Air dashes"));
+                }
+            });
+        egui::CollapsingHeader::new("Ledge properties")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add(egui::Checkbox::new(
+                    &mut self.can_ledge_grab,
+                    "Allow grabbing ledges",
+                ));
+                if self.can_ledge_grab {
                     ui.add(egui::Checkbox::new(
-                        &mut allow_wallsliding,
-                        "Allow wallsliding",
+                        &mut self.ledge_auto_climb,
+                        "Climb ledges automatically",
                     ));
-                    if !allow_wallsliding {
-                        self.wallslide_max_v_speed = None;
-                    } else {
-                        let mut wallslide_max_v_speed = self.wallslide_max_v_speed.unwrap_or(15.);
-                        ui.add(
-                            egui::Slider::new(&mut wallslide_max_v_speed, 0f32..=100.)
-                                .text("Wallslide max vertical speed"),
+                    if self.ledge_auto_climb {
+                        duration_slider(
+                            ui,
+                            &mut self.ledge_climb_delay,
+                            2000,
+                            "Ledge climb delay (ms)",
                         );
-                        self.wallslide_max_v_speed = Some(wallslide_max_v_speed);
                     }
                     ui.add(egui::Checkbox::new(
-                        &mut self.can_walljump,
-                        "Allow walljumps",
+                        &mut self.ledge_climb_on_input,
+                        "Climb ledges by holding up",
                     ));
-                    if self.can_walljump {
-                        ui.add(
-                            egui::Slider::new(&mut self.walljump_horizontal_force, 0f32..=100.)
-                                .text("Walljump horizontal force"),
-                        );
-                        ui.add(
-                            egui::Slider::new(&mut self.walljump_vertical_force, 0f32..=100.)
-                                .text("Walljump vertical force"),
-                        );
-                    }
-                });
-        });
+                    ui.add(egui::Checkbox::new(
+                        &mut self.can_ledge_drop,
+                        "Drop from ledges by holding down",
+                    ));
+                    ui.add(egui::Checkbox::new(
+                        &mut self.can_ledge_jump,
+                        "Allow jumping from ledges",
+                    ));
+                }
+            });
     }
 }
 
+fn duration_slider(ui: &mut egui::Ui, duration: &mut Duration, max_ms: u64, text: &str) {
+    let mut ms = duration.as_millis() as u64;
+    ui.add(egui::Slider::new(&mut ms, 0..=max_ms).text(text));
+    *duration = Duration::from_millis(ms);
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum SlideSide {
     /// Sliding against a wall which is to the right of the player.
@@ -189,7 +344,14 @@ pub enum SlideSide {
 pub enum State {
     Grounded,
     Airborne,
-    Sliding { side: SlideSide },
+    Sliding {
+        side: SlideSide,
+    },
+    Dashing,
+    /// Hanging from the top of the wall on the given side.
+    LedgeHang {
+        side: SlideSide,
+    },
 }
 
 impl Default for State {
@@ -206,7 +368,42 @@ pub struct RoomPos {
 
 #[derive(Component)]
 pub struct CurrentRoom {
-    id: String,
+    /// Path of the world the level belongs to, as in [`WorldRegistry`].
+    pub world: String,
+    /// Identifier of the level the entity is in.
+    pub id: String,
+}
+
+impl CurrentRoom {
+    fn is(&self, world: &str, level: &Level) -> bool {
+        self.world == world && self.id == level.identifier
+    }
+}
+
+/// Sent when an entity with a [`RoomPos`] moves into a level.
+#[derive(Debug, Clone)]
+pub struct RoomEntered {
+    pub entity: Entity,
+    /// Identifier of the level entered.
+    pub room: String,
+    /// Identifier of the level the entity came from, or `None` if it was just spawned.
+    pub from: Option<String>,
+    /// Whether the entity was teleported into the level instead of walking in.
+    pub teleported: bool,
+}
+
+/// Moves the player to the given position, in bevy units.
+#[derive(Debug, Clone)]
+pub struct TeleportPlayer {
+    pub position: Vec2,
+}
+
+/// Sent when an entity with a [`RoomPos`] moves out of a level.
+#[derive(Debug, Clone)]
+pub struct RoomExited {
+    pub entity: Entity,
+    /// Identifier of the level exited.
+    pub room: String,
 }
 
 #[derive(Component, Default, Debug)]
@@ -220,14 +417,80 @@ pub struct Player {
     last_grounded_time: Duration,
     last_walljump_time: Duration,
     times_jumped_since_grounded: u32,
+    /// Time at which the player started clinging to the wall it is on.
+    wall_stick_start: Option<Duration>,
+    /// Stamina spent holding onto walls since the player last landed.
+    wall_stamina_used: Duration,
+    facing_left: bool,
+    /// Whether the player was dashing on the last frame.
+    dashing: bool,
+    dash_direction: Vec2,
+    /// Time at which the current or last dash ends.
+    dash_end: Duration,
+    air_dashes_used: u32,
+    ledge_grab_time: Duration,
+    ledge_release_time: Duration,
+    /// Whether there is room to climb up the ledge the player is hanging from.
+    can_climb_ledge: bool,
+    crouching: bool,
+    /// Whether there is room for the player to stand up while crouching.
+    can_stand_up: bool,
     left_side_sensor: Option<Entity>,
     right_side_sensor: Option<Entity>,
+    left_ledge_sensor: Option<Entity>,
+    right_ledge_sensor: Option<Entity>,
+    left_climb_sensor: Option<Entity>,
+    right_climb_sensor: Option<Entity>,
+    headroom_sensor: Option<Entity>,
+}
+
+impl Player {
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn properties(&self) -> &PlayerProperties {
+        &self.properties
+    }
+
+    pub fn properties_mut(&mut self) -> &mut PlayerProperties {
+        &mut self.properties
+    }
+
+    pub fn is_crouching(&self) -> bool {
+        self.crouching
+    }
+
+    /// Resets the movement state of the player, such as jump counters and dashes, keeping its
+    /// properties and sensors.
+    pub fn reset(&mut self) {
+        *self = Self {
+            properties: std::mem::take(&mut self.properties),
+            left_side_sensor: self.left_side_sensor,
+            right_side_sensor: self.right_side_sensor,
+            left_ledge_sensor: self.left_ledge_sensor,
+            right_ledge_sensor: self.right_ledge_sensor,
+            left_climb_sensor: self.left_climb_sensor,
+            right_climb_sensor: self.right_climb_sensor,
+            headroom_sensor: self.headroom_sensor,
+            ..default()
+        };
+    }
+
+    fn can_dash(&self, time: Duration) -> bool {
+        self.properties.can_dash
+            && self.state != State::Dashing
+            && time >= self.dash_end + self.properties.dash_cooldown
+            && (self.state == State::Grounded || self.air_dashes_used < self.properties.air_dashes)
+    }
 }
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
     #[bundle]
-    sprite: SpriteBundle,
+    sprite: SpriteSheetBundle,
+    animation: PlayerAnimation,
+    animator: Animator,
     velocity: Velocity,
     body: KinematicBody,
     player: Player,
@@ -238,21 +501,29 @@ pub struct PlayerBundle {
 impl Default for PlayerBundle {
     fn default() -> Self {
         Self {
-            sprite: SpriteBundle {
+            sprite: SpriteSheetBundle {
                 transform: Transform::from_xyz(0., 0., 10.),
                 ..default()
             },
+            animation: default(),
+            animator: default(),
             velocity: default(),
             body: default(),
             player: default(),
             collision: RectCollision {
-                rect: Rect::from_min_size(vec2(0., 0.), vec2(1., 1.)),
+                rect: player_collider(false),
             },
             room_pos: default(),
         }
     }
 }
 
+/// Collider of the player, which is half as tall while crouching.
+fn player_collider(crouching: bool) -> Rect {
+    let height = if crouching { 0.5 } else { 1. };
+    Rect::from_min_size(vec2(0., 0.), vec2(1., height))
+}
+
 #[derive(Bundle)]
 struct PlayerSideCollisionCheckerBundle {
     global_transform: GlobalTransform,
@@ -287,44 +558,72 @@ impl PlayerSideCollisionCheckerBundle {
             ..default()
         }
     }
+
+    /// Checks for walls beside the head of the player. A wall beside the player but not beside
+    /// their head is a ledge.
+    pub fn ledge(side: SlideSide) -> Self {
+        Self {
+            collision: RectCollision {
+                rect: Rect::from_min_size(vec2(0., 0.5), vec2(1., 0.5)),
+            },
+            ..Self::side(side)
+        }
+    }
+
+    /// Checks the spot the player climbs to from a ledge, relative to where they hang from it.
+    pub fn climb(side: SlideSide) -> Self {
+        let x = match side {
+            SlideSide::Left => -1.,
+            SlideSide::Right => 1.,
+        };
+        Self {
+            transform: Transform::from_xyz(x, 0.5, 0.),
+            ..default()
+        }
+    }
+
+    /// Checks for room to stand up in while crouching.
+    pub fn headroom() -> Self {
+        Self {
+            collision: RectCollision {
+                rect: player_collider(false),
+            },
+            ..default()
+        }
+    }
+
+    fn side(side: SlideSide) -> Self {
+        match side {
+            SlideSide::Left => Self::left(),
+            SlideSide::Right => Self::right(),
+        }
+    }
 }
 
 pub fn spawn_player(
     mut commands: Commands,
     world: Res<GameWorld>,
     player_query: Query<(), With<Player>>,
-    ldtk_maps: Res<Assets<LdtkProject>>,
+    ldtk_maps: Res<Assets<WorldProject>>,
+    animations: Res<PlayerAnimations>,
 ) {
     if !player_query.is_empty() {
         return;
     }
 
-    let map = ldtk_maps
-        .get(&world.ldtk)
-        .expect("Player was spawned before project was loaded in");
-
-    let start_point_def = map
-        .project
-        .defs
-        .entities
-        .iter()
-        .find(|def| def.identifier == "Start_Point")
-        .expect("Could not find Start_Point entity definition");
+    let map = if let Some(map) = ldtk_maps.get(&world.project) {
+        map
+    } else {
+        error!("Player was spawned before project was loaded in");
+        return;
+    };
 
-    let (start_level, start_point) = map
-        .project
-        .levels
-        .iter()
-        .find_map(|level| {
-            level
-                .layer_instances
-                .iter()
-                .flatten()
-                .flat_map(|layer| layer.entity_instances.iter())
-                .find(|&entity| entity.def_uid == start_point_def.uid)
-                .map(|entity| (level, entity))
-        })
-        .expect("Could not find world start point");
+    let (start_level, start_point) = if let Some(start) = map.find_entity(START_POINT_IDENTIFIER) {
+        start
+    } else {
+        error!("Could not find world start point");
+        return;
+    };
 
     let px = (start_point.px[0] + start_level.world_x) as f32 / 16.;
     let py = -(start_point.px[1] + start_level.world_y) as f32 / 16.;
@@ -335,12 +634,16 @@ pub fn spawn_player(
 
     let mut left_id = None;
     let mut right_id = None;
+    let mut ledge_ids = [None; 2];
+    let mut climb_ids = [None; 2];
+    let mut headroom_id = None;
     commands
         .spawn_bundle(PlayerBundle {
-            sprite: SpriteBundle {
+            sprite: SpriteSheetBundle {
                 transform,
                 ..default()
             },
+            animator: Animator::new(animations.set.clone(), PlayerAnimation::Idle.clip_name()),
             ..default()
         })
         .with_children(|children| {
@@ -354,47 +657,94 @@ pub fn spawn_player(
                     .spawn_bundle(PlayerSideCollisionCheckerBundle::right())
                     .id(),
             );
+            for (i, side) in [SlideSide::Left, SlideSide::Right].into_iter().enumerate() {
+                ledge_ids[i] = Some(
+                    children
+                        .spawn_bundle(PlayerSideCollisionCheckerBundle::ledge(side))
+                        .id(),
+                );
+                climb_ids[i] = Some(
+                    children
+                        .spawn_bundle(PlayerSideCollisionCheckerBundle::climb(side))
+                        .id(),
+                );
+            }
+            headroom_id = Some(
+                children
+                    .spawn_bundle(PlayerSideCollisionCheckerBundle::headroom())
+                    .id(),
+            );
         })
         .insert(Player {
             left_side_sensor: left_id,
             right_side_sensor: right_id,
+            left_ledge_sensor: ledge_ids[0],
+            right_ledge_sensor: ledge_ids[1],
+            left_climb_sensor: climb_ids[0],
+            right_climb_sensor: climb_ids[1],
+            headroom_sensor: headroom_id,
             ..default()
         });
 }
 
+/// Returns the area covered by a level, in bevy units.
+fn level_bounds(level: &Level) -> Rect {
+    const TILE_SIZE: f32 = crate::world::TILE_SIZE as f32;
+
+    Rect::from_min_size(
+        vec2(
+            level.world_x as f32 / TILE_SIZE,
+            -(level.world_y + level.px_hei) as f32 / TILE_SIZE,
+        ),
+        vec2(
+            level.px_wid as f32 / TILE_SIZE,
+            level.px_hei as f32 / TILE_SIZE,
+        ),
+    )
+}
+
+/// Returns whether an entity at the given position is considered to be inside a level.
+pub fn is_in_level(level: &Level, position: Vec2) -> bool {
+    let bounds = level_bounds(level);
+    // Sprites are centered, so extend the bounds by half a tile on each side
+    Rect {
+        min: bounds.min - vec2(0.5, 0.5),
+        max: bounds.max + vec2(0.5, 0.5),
+    }
+    .contains(position)
+}
+
+impl RoomPos {
+    /// Returns the world grid cell an entity at the given position is in.
+    pub fn at(position: Vec2, grid_width: i64, grid_height: i64) -> Self {
+        Self {
+            x: ((position.x - 0.5).floor() * TILE_SIZE as f32 / grid_width as f32).floor() as i64,
+            y: (position.y.round() * TILE_SIZE as f32 / grid_height as f32).floor() as i64,
+        }
+    }
+}
+
 fn update_camera_bounds(
     world: Res<GameWorld>,
-    maps: Res<Assets<LdtkProject>>,
+    maps: Res<Assets<WorldProject>>,
     current_room: Query<&CurrentRoom, With<Player>>,
     mut camera: Query<&mut CameraFollow, With<Camera>>,
 ) {
-    let map = if let Some(map) = maps.get(&world.ldtk) {
+    let map = if let Some(map) = maps.get(&world.project) {
         map
     } else {
         return;
     };
 
-    const TILE_SIZE: f32 = crate::world::TILE_SIZE as f32;
-
     if let Ok(current_room) = current_room.get_single() {
-        let level = map
-            .project
-            .levels
-            .iter()
-            .find(|level| level.identifier == current_room.id)
-            .unwrap();
+        let level = if let Some(level) = map.level(&current_room.id) {
+            level
+        } else {
+            return;
+        };
 
         if let Ok(mut follow) = camera.get_single_mut() {
-            follow.bounds = Rect::from_min_size(
-                vec2(
-                    level.world_x as f32 / TILE_SIZE,
-                    -(level.world_y + level.px_hei) as f32 / TILE_SIZE,
-                ),
-                vec2(
-                    level.px_wid as f32 / TILE_SIZE,
-                    level.px_hei as f32 / TILE_SIZE,
-                ),
-            );
+            follow.bounds = level_bounds(level);
         }
     }
 }
@@ -402,37 +752,43 @@ fn update_camera_bounds(
 fn update_current_room(
     mut commands: Commands,
     world: Res<GameWorld>,
-    maps: Res<Assets<LdtkProject>>,
+    registry: Res<WorldRegistry>,
+    maps: Res<Assets<WorldProject>>,
     mut query: Query<(Entity, &GlobalTransform, Option<&CurrentRoom>), Changed<RoomPos>>,
+    mut entered_events: EventWriter<RoomEntered>,
+    mut exited_events: EventWriter<RoomExited>,
 ) {
-    let map = if let Some(map) = maps.get(&world.ldtk) {
+    let map = if let Some(map) = maps.get(&world.project) {
         map
     } else {
         return;
     };
 
-    const TILE_SIZE: f32 = crate::world::TILE_SIZE as f32;
+    let world_path = registry.current.as_deref().unwrap_or_default();
 
     for (entity, transform, current_room) in query.iter_mut() {
-        for level in map.project.levels.iter().filter(|level| {
-            current_room
-                .map(|room| level.identifier != room.id)
-                .unwrap_or(true)
-        }) {
-            let level_rect = Rect::from_min_size(
-                vec2(
-                    level.world_x as f32 / TILE_SIZE - 0.5,
-                    -(level.world_y + level.px_hei) as f32 / TILE_SIZE - 0.5,
-                ),
-                vec2(
-                    level.px_wid as f32 / TILE_SIZE + 1.,
-                    level.px_hei as f32 / TILE_SIZE + 1.,
-                ),
-            );
-            if level_rect.contains(transform.translation.truncate()) {
+        for level in map
+            .levels
+            .iter()
+            .filter(|level| !current_room.map_or(false, |room| room.is(world_path, level)))
+        {
+            if is_in_level(level, transform.translation.truncate()) {
                 commands.entity(entity).insert(CurrentRoom {
+                    world: world_path.to_owned(),
                     id: level.identifier.clone(),
                 });
+                if let Some(current_room) = current_room {
+                    exited_events.send(RoomExited {
+                        entity,
+                        room: current_room.id.clone(),
+                    });
+                }
+                entered_events.send(RoomEntered {
+                    entity,
+                    room: level.identifier.clone(),
+                    from: current_room.map(|room| room.id.clone()),
+                    teleported: false,
+                });
                 break;
             }
         }
@@ -441,31 +797,106 @@ fn update_current_room(
 
 fn update_room_pos(
     world: Res<GameWorld>,
-    maps: Res<Assets<LdtkProject>>,
+    maps: Res<Assets<WorldProject>>,
     mut query: Query<(&mut RoomPos, &GlobalTransform), Changed<GlobalTransform>>,
 ) {
-    let map = if let Some(map) = maps.get(&world.ldtk) {
+    let (grid_width, grid_height) = match maps.get(&world.project).and_then(|map| map.world_grid) {
+        Some(grid) => grid,
+        _ => return,
+    };
+
+    for (mut room_pos, transform) in query.iter_mut() {
+        let according_pos = RoomPos::at(transform.translation.truncate(), grid_width, grid_height);
+        if according_pos != *room_pos {
+            *room_pos = according_pos;
+        }
+    }
+}
+
+/// Moves the player to the position given by each [`TeleportPlayer`] event.
+///
+/// Room tracking and camera bounds are updated right away, instead of waiting for the transform of
+/// the player to propagate.
+pub fn teleport_player(
+    mut commands: Commands,
+    mut teleport_events: EventReader<TeleportPlayer>,
+    world: Res<GameWorld>,
+    registry: Res<WorldRegistry>,
+    maps: Res<Assets<WorldProject>>,
+    mut player: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut RoomPos,
+            Option<&CurrentRoom>,
+        ),
+        With<Player>,
+    >,
+    mut camera: Query<&mut CameraFollow, With<Camera>>,
+    mut entered_events: EventWriter<RoomEntered>,
+    mut exited_events: EventWriter<RoomExited>,
+) {
+    let map = if let Some(map) = maps.get(&world.project) {
         map
     } else {
         return;
     };
 
-    for (mut room_pos, transform) in query.iter_mut() {
-        let get_pos = |point: Vec2| -> RoomPos {
-            RoomPos {
-                x: ((point.x - 0.5).floor() * TILE_SIZE as f32
-                    / map.project.world_grid_width.unwrap() as f32)
-                    .floor() as i64,
-                y: (point.y.round() * TILE_SIZE as f32
-                    / map.project.world_grid_height.unwrap() as f32)
-                    .floor() as i64,
-            }
+    let (entity, mut transform, mut velocity, mut room_pos, current_room) =
+        if let Ok(player) = player.get_single_mut() {
+            player
+        } else {
+            return;
         };
 
-        let according_pos = get_pos(transform.translation.truncate());
-        if according_pos != *room_pos {
-            *room_pos = according_pos;
+    for event in teleport_events.iter() {
+        info!("Teleporting player to {:?}", event.position);
+        transform.translation.x = event.position.x;
+        transform.translation.y = event.position.y;
+        **velocity = Vec2::ZERO;
+
+        if let Some((grid_width, grid_height)) = map.world_grid {
+            *room_pos = RoomPos::at(event.position, grid_width, grid_height);
         }
+
+        let level = if let Some(level) = map
+            .levels
+            .iter()
+            .find(|level| is_in_level(level, event.position))
+        {
+            level
+        } else {
+            warn!("Teleported player outside of any level");
+            continue;
+        };
+
+        if let Ok(mut follow) = camera.get_single_mut() {
+            follow.bounds = level_bounds(level);
+            follow.transition = None;
+        }
+
+        // Levels of different worlds may share an identifier
+        let world_path = registry.current.as_deref().unwrap_or_default();
+        if current_room.map_or(false, |room| room.is(world_path, level)) {
+            continue;
+        }
+        commands.entity(entity).insert(CurrentRoom {
+            world: world_path.to_owned(),
+            id: level.identifier.clone(),
+        });
+        if let Some(current_room) = current_room {
+            exited_events.send(RoomExited {
+                entity,
+                room: current_room.id.clone(),
+            });
+        }
+        entered_events.send(RoomEntered {
+            entity,
+            room: level.identifier.clone(),
+            from: current_room.map(|room| room.id.clone()),
+            teleported: true,
+        });
     }
 }
 
@@ -492,13 +923,26 @@ fn update_player(
     time: Res<Time>,
     gameplay_time: Res<GameplayTime>,
     input: Res<Input>,
-    mut player: Query<(&mut Velocity, &mut Player, &mut KinematicBody)>,
+    frozen: Res<PhysicsFrozen>,
+    environment: Res<LevelEnvironment>,
+    mut player: Query<(
+        &mut Velocity,
+        &mut Player,
+        &mut KinematicBody,
+        &mut Transform,
+        &mut RectCollision,
+    )>,
 ) {
-    let (mut velocity, mut player, mut body) = if let Ok(player) = player.get_single_mut() {
-        player
-    } else {
+    if frozen.0 {
         return;
-    };
+    }
+
+    let (mut velocity, mut player, mut body, mut transform, mut collision) =
+        if let Ok(player) = player.get_single_mut() {
+            player
+        } else {
+            return;
+        };
     let delta = time.delta_seconds();
     let unpaused_time = gameplay_time.elapsed();
 
@@ -508,18 +952,133 @@ fn update_player(
     let pressing_jump = input.actions[input_mapper::Action::Jump].is_pressed();
     body.pass_through_platforms = pressing_down && pressing_jump;
 
+    // Crouch while holding down on the ground, and only stand up with room overhead
+    if player.properties.can_crouch
+        && pressing_down
+        && !body.pass_through_platforms
+        && player.state == State::Grounded
+    {
+        player.crouching = true;
+    } else if player.crouching && player.can_stand_up {
+        player.crouching = false;
+    }
+    collision.rect = player_collider(player.crouching);
+
     let pressing_jump = !pressing_down && pressing_jump;
 
+    if x_input != 0. {
+        player.facing_left = x_input < 0.;
+    }
+
+    // Start dashing
+    if input.actions[input_mapper::Action::Dash] == input_mapper::ActionState::JustPressed
+        && player.can_dash(unpaused_time)
+    {
+        let y_input = match player.properties.dash_directions {
+            DashDirections::Horizontal => 0.,
+            DashDirections::Eight => input.axes[input_mapper::Axis::Vertical].value(),
+        };
+        // Snap analog input to the eight directions
+        let snap = |value: f32| {
+            if value.abs() > 0.5 {
+                value.signum()
+            } else {
+                0.
+            }
+        };
+        let mut direction = vec2(snap(x_input), snap(y_input));
+        if direction == Vec2::ZERO {
+            direction.x = if player.facing_left { -1. } else { 1. };
+        }
+        if player.state != State::Grounded {
+            player.air_dashes_used += 1;
+        }
+        player.dash_direction = direction.normalize();
+        player.dash_end = unpaused_time + player.properties.dash_duration;
+        player.dashing = true;
+        player.state = State::Dashing;
+        **velocity = player.dash_direction * player.properties.dash_speed;
+    } else if player.dashing && player.state != State::Dashing {
+        // Don't keep the speed of the dash once it ends
+        player.dashing = false;
+        velocity.x = velocity.x.clamp(
+            -player.properties.max_run_speed,
+            player.properties.max_run_speed,
+        );
+        velocity.y = velocity.y.min(0.);
+    }
+
+    // Hang from ledges until climbing up, dropping down or jumping off
+    if let State::LedgeHang { side } = player.state {
+        let properties = &player.properties;
+        let climb = (properties.ledge_auto_climb
+            && unpaused_time >= player.ledge_grab_time + properties.ledge_climb_delay)
+            || (properties.ledge_climb_on_input
+                && input.axes[input_mapper::Axis::Vertical].value() > 0.5);
+        let let_go = properties.can_ledge_drop && pressing_down;
+        let jump = properties.can_ledge_jump && player.pressed_jump;
+
+        if climb && player.can_climb_ledge {
+            let x = match side {
+                SlideSide::Left => -1.,
+                SlideSide::Right => 1.,
+            };
+            transform.translation += vec3(x, 0.5, 0.);
+        } else if jump {
+            player.pressed_jump = false;
+            velocity.y = player.properties.jump_force;
+            player.times_jumped_since_grounded = 1;
+            player.can_jump = player.properties.jumps_available > 1;
+        }
+        if (climb && player.can_climb_ledge) || let_go || jump {
+            player.ledge_release_time = unpaused_time;
+            player.state = State::Airborne;
+        }
+    }
+
     // Apply gravity
+    let dash_gravity_scale = match player.state {
+        State::Dashing => player.properties.dash_gravity_scale,
+        State::LedgeHang { .. } => 0.,
+        _ => 1.,
+    };
     velocity.y -= if pressing_jump && velocity.y > 0. {
         player.properties.jump_gravity
     } else {
         player.properties.gravity
-    } * delta;
+    } * environment.gravity_scale
+        * dash_gravity_scale
+        * delta;
     if f32::abs(velocity.y) > player.properties.terminal_speed {
         velocity.y = player.properties.terminal_speed * velocity.y.signum();
     }
 
+    // Cling to walls, first for the grip time and then while holding toward the wall
+    match player.state {
+        State::Sliding { side } if player.properties.can_wall_stick && velocity.y <= 0. => {
+            let stick_start = *player.wall_stick_start.get_or_insert(unpaused_time);
+            let gripping = unpaused_time < stick_start + player.properties.wall_grip_time;
+            let holding = match side {
+                SlideSide::Left => x_input < 0.,
+                SlideSide::Right => x_input > 0.,
+            } && player.wall_stamina_used < player.properties.wall_stick_stamina;
+            if gripping || holding {
+                if !gripping {
+                    player.wall_stamina_used += time.delta();
+                }
+                velocity.y = 0.;
+            }
+        }
+        State::Sliding { .. } => (),
+        State::Grounded => {
+            player.wall_stick_start = None;
+            player.wall_stamina_used = Duration::ZERO;
+        }
+        State::Airborne | State::Dashing | State::LedgeHang { .. } => {
+            player.wall_stick_start = None
+        }
+    }
+
     // Clamp velocity if sliding down wall
     if matches!(player.state, State::Sliding { .. }) {
         if let Some(wallslide_max_v_speed) = player.properties.wallslide_max_v_speed {
@@ -529,12 +1088,16 @@ fn update_player(
         }
     }
 
-    if x_input == 0. {
+    if player.state == State::Dashing {
+        velocity.x = player.dash_direction.x * player.properties.dash_speed;
+    } else if let State::LedgeHang { .. } = player.state {
+        **velocity = Vec2::ZERO;
+    } else if x_input == 0. {
         // Apply horizontal decceleration
         let decceleration = match player.state {
             State::Grounded => player.properties.ground_decceleration,
             State::Airborne => player.properties.air_decceleration,
-            State::Sliding { .. } => 0.,
+            State::Sliding { .. } | State::Dashing | State::LedgeHang { .. } => 0.,
         } * delta;
 
         if f32::abs(velocity.x) > decceleration {
@@ -552,21 +1115,30 @@ fn update_player(
         let acceleration = if x_input.signum() != velocity.x.signum() {
             match player.state {
                 State::Grounded => player.properties.ground_direction_change_acceleration,
-                State::Airborne | State::Sliding { .. } => {
-                    player.properties.air_direction_change_acceleration
-                }
+                State::Airborne
+                | State::Sliding { .. }
+                | State::Dashing
+                | State::LedgeHang { .. } => player.properties.air_direction_change_acceleration,
             }
         } else {
             match player.state {
                 State::Grounded => player.properties.ground_acceleration,
-                State::Airborne | State::Sliding { .. } => player.properties.air_acceleration,
+                State::Airborne
+                | State::Sliding { .. }
+                | State::Dashing
+                | State::LedgeHang { .. } => player.properties.air_acceleration,
             }
         } * delta;
 
         velocity.x += x_input * acceleration;
 
-        if f32::abs(velocity.x) > player.properties.max_run_speed {
-            velocity.x = player.properties.max_run_speed * velocity.x.signum();
+        let max_speed = if player.crouching {
+            player.properties.crawl_speed
+        } else {
+            player.properties.max_run_speed
+        };
+        if f32::abs(velocity.x) > max_speed {
+            velocity.x = max_speed * velocity.x.signum();
         }
     }
 
@@ -576,6 +1148,7 @@ fn update_player(
                 player.can_jump = true;
             }
             player.times_jumped_since_grounded = 0;
+            player.air_dashes_used = 0;
             player.last_grounded_time = time.time_since_startup();
         }
         State::Airborne
@@ -595,6 +1168,8 @@ fn update_player(
     // Handle jumping/walljumping
     if player.pressed_jump {
         match (player.properties.can_walljump, player.state) {
+            // Jumping off ledges is handled above
+            (_, State::LedgeHang { .. }) => (),
             (true, State::Sliding { side }) => {
                 player.pressed_jump = false;
                 player.last_walljump_time = unpaused_time;
@@ -629,13 +1204,63 @@ fn update_player(
     }
 }
 
+/// Time after letting go of a ledge during which ledges can't be grabbed.
+const LEDGE_REGRAB_DELAY: Duration = Duration::from_millis(300);
+
 fn set_player_state(
-    mut query: Query<(&mut Player, &KinematicCollisions)>,
+    gameplay_time: Res<GameplayTime>,
+    mut query: Query<(&mut Player, &KinematicCollisions, &Velocity, &mut Transform)>,
     sensors: Query<&SensedBodies, With<SensorBody>>,
 ) {
-    if let Ok((mut player, collisions)) = query.get_single_mut() {
-        if collisions.sides.contains(CollisionSide::DOWN) {
+    let senses_world = |sensor: Option<Entity>| {
+        sensor
+            .and_then(|sensor| sensors.get(sensor).ok())
+            .map_or(false, |sensed| sensed.world)
+    };
+
+    if let Ok((mut player, collisions, velocity, mut transform)) = query.get_single_mut() {
+        let now = gameplay_time.elapsed();
+        player.can_stand_up = !senses_world(player.headroom_sensor);
+        // A wall beside the player with nothing above it
+        let ledge = [
+            (
+                SlideSide::Left,
+                player.left_side_sensor,
+                player.left_ledge_sensor,
+            ),
+            (
+                SlideSide::Right,
+                player.right_side_sensor,
+                player.right_ledge_sensor,
+            ),
+        ]
+        .into_iter()
+        .find(|(_, side_sensor, ledge_sensor)| {
+            senses_world(*side_sensor) && !senses_world(*ledge_sensor)
+        })
+        .map(|(side, _, _)| side);
+
+        if now < player.dash_end {
+            player.state = State::Dashing;
+        } else if let State::LedgeHang { side } = player.state {
+            // Keep hanging until the player lets go
+            let climb_sensor = match side {
+                SlideSide::Left => player.left_climb_sensor,
+                SlideSide::Right => player.right_climb_sensor,
+            };
+            player.can_climb_ledge = !senses_world(climb_sensor);
+        } else if collisions.sides.contains(CollisionSide::DOWN) {
             player.state = State::Grounded;
+        } else if let Some(side) = ledge.filter(|_| {
+            player.properties.can_ledge_grab
+                && velocity.y <= 0.
+                && now >= player.ledge_release_time + LEDGE_REGRAB_DELAY
+        }) {
+            // Line the head of the player up with the top of the ledge
+            transform.translation.y = (transform.translation.y + 0.5).floor();
+            player.state = State::LedgeHang { side };
+            player.ledge_grab_time = now;
+            player.can_climb_ledge = false;
         } else if sensors.get(player.left_side_sensor.unwrap()).unwrap().world {
             player.state = State::Sliding {
                 side: SlideSide::Left,
@@ -0,0 +1,132 @@
+//! Chooses the animation of the player from its movement state and velocity.
+//!
+//! The clips themselves are defined in `assets/player.anim.ron`.
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::{animate_sprites, AnimationSet, Animator},
+    physics::Velocity,
+    player::{Player, SlideSide, State},
+    AppState,
+};
+
+pub const PLAYER_ANIMATIONS: &str = "player.anim.ron";
+/// Horizontal speed over which the player is considered to be moving, in units per second.
+const MOVING_THRESHOLD: f32 = 0.5;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayerAnimation {
+    Idle,
+    Run,
+    JumpRise,
+    Fall,
+    Land,
+    WallSlide,
+    WallJump,
+    Dash,
+    LedgeHang,
+    Crouch,
+    Crawl,
+}
+
+impl Default for PlayerAnimation {
+    fn default() -> Self {
+        PlayerAnimation::Idle
+    }
+}
+
+impl PlayerAnimation {
+    /// Name of the clip played for this animation in the player's animation set.
+    pub fn clip_name(self) -> &'static str {
+        match self {
+            PlayerAnimation::Idle => "idle",
+            PlayerAnimation::Run => "run",
+            PlayerAnimation::JumpRise => "jump_rise",
+            PlayerAnimation::Fall => "fall",
+            PlayerAnimation::Land => "land",
+            PlayerAnimation::WallSlide => "wall_slide",
+            PlayerAnimation::WallJump => "wall_jump",
+            PlayerAnimation::Dash => "dash",
+            PlayerAnimation::LedgeHang => "ledge_hang",
+            PlayerAnimation::Crouch => "crouch",
+            PlayerAnimation::Crawl => "crawl",
+        }
+    }
+
+    /// Returns the animation to play after this one, given the state of the player.
+    fn next(self, finished: bool, state: State, crouching: bool, velocity: Vec2) -> Self {
+        use PlayerAnimation::*;
+
+        let moving = velocity.x.abs() > MOVING_THRESHOLD;
+        match state {
+            State::Dashing => Dash,
+            State::LedgeHang { .. } => LedgeHang,
+            State::Grounded => match self {
+                JumpRise | Fall | WallSlide | WallJump => Land,
+                // Running or crouching cuts the landing short
+                Land if !finished && !moving && !crouching => Land,
+                _ if crouching && moving => Crawl,
+                _ if crouching => Crouch,
+                _ if moving => Run,
+                _ => Idle,
+            },
+            // Clinging to a wall counts as sliding
+            State::Sliding { .. } if velocity.y <= 0. => WallSlide,
+            State::Airborne | State::Sliding { .. } => match self {
+                WallSlide | WallJump if velocity.y > 0. => WallJump,
+                _ if velocity.y > 0. => JumpRise,
+                _ => Fall,
+            },
+        }
+    }
+}
+
+pub struct PlayerAnimations {
+    pub set: Handle<AnimationSet>,
+}
+
+pub struct PlayerAnimationPlugin;
+
+impl Plugin for PlayerAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_player_animations)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(choose_player_animation.before(animate_sprites)),
+            );
+    }
+}
+
+fn load_player_animations(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PlayerAnimations {
+        set: asset_server.load(PLAYER_ANIMATIONS),
+    });
+}
+
+fn choose_player_animation(
+    mut query: Query<(&Player, &Velocity, &mut PlayerAnimation, &mut Animator)>,
+) {
+    for (player, velocity, mut animation, mut animator) in query.iter_mut() {
+        let state = player.state();
+        let next = animation.next(
+            animator.is_finished(),
+            state,
+            player.is_crouching(),
+            **velocity,
+        );
+        if next != *animation {
+            *animation = next;
+            animator.play(next.clip_name());
+        }
+
+        animator.flip_x = match state {
+            // Face away from the wall, which is where wall jumps go
+            State::Sliding { side } if velocity.y <= 0. => side == SlideSide::Right,
+            State::LedgeHang { side } => side == SlideSide::Left,
+            _ if velocity.x > MOVING_THRESHOLD => false,
+            _ if velocity.x < -MOVING_THRESHOLD => true,
+            _ => animator.flip_x,
+        };
+    }
+}
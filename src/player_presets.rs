@@ -0,0 +1,205 @@
+//! Named presets of [`PlayerProperties`], loaded from `assets/player.props.ron`.
+//!
+//! Ctrl+I opens a window to tune the properties of the player while the game runs, switch between
+//! presets and save the current values as a preset. Changes to the file are applied right away.
+
+use std::{collections::BTreeMap, path::Path};
+
+use bevy::{
+    asset::{AssetLoader, AssetServerSettings, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{Player, PlayerProperties};
+
+/// Path of the presets file, relative to the assets folder.
+pub const PLAYER_PRESETS: &str = "player.props.ron";
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "c2e5f0a4-7b39-4d8e-9f16-5a0b3d7e4c21"]
+pub struct PlayerPresets {
+    /// Preset the player starts with.
+    pub default: String,
+    pub presets: BTreeMap<String, PlayerProperties>,
+}
+
+pub struct PlayerPresetsLoader;
+
+impl AssetLoader for PlayerPresetsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let loaded_asset = LoadedAsset::new(ron::de::from_bytes::<PlayerPresets>(bytes)?);
+            load_context.set_default_asset(loaded_asset);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["props.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerPresetSettings {
+    pub presets: Handle<PlayerPresets>,
+    /// Preset applied to the player, or `None` for the default one.
+    pub current: Option<String>,
+    window_open: bool,
+    /// Name to save the current properties under.
+    preset_name: String,
+    /// Result of the last save.
+    status: Option<String>,
+}
+
+impl PlayerPresetSettings {
+    /// Returns the properties of the current preset, if it exists.
+    fn current_properties<'a>(&self, presets: &'a PlayerPresets) -> Option<&'a PlayerProperties> {
+        let name = self.current.as_ref().unwrap_or(&presets.default);
+        presets.presets.get(name)
+    }
+}
+
+pub struct PlayerPresetsPlugin;
+
+impl Plugin for PlayerPresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<PlayerPresets>()
+            .add_asset_loader(PlayerPresetsLoader)
+            .init_resource::<PlayerPresetSettings>()
+            .add_startup_system(load_player_presets)
+            .add_system(apply_player_preset)
+            .add_system(show_player_properties_window.after(apply_player_preset));
+    }
+}
+
+fn load_player_presets(asset_server: Res<AssetServer>, mut settings: ResMut<PlayerPresetSettings>) {
+    settings.presets = asset_server.load(PLAYER_PRESETS);
+}
+
+/// Gives the current preset to newly spawned players, and to every player when the presets file
+/// changes.
+fn apply_player_preset(
+    mut events: EventReader<AssetEvent<PlayerPresets>>,
+    settings: Res<PlayerPresetSettings>,
+    presets: Res<Assets<PlayerPresets>>,
+    mut players: Query<(&mut Player, ChangeTrackers<Player>)>,
+) {
+    let reloaded = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == settings.presets
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+
+    let properties = match presets
+        .get(&settings.presets)
+        .and_then(|presets| settings.current_properties(presets))
+    {
+        Some(properties) => properties,
+        None => return,
+    };
+    for (mut player, tracker) in players.iter_mut() {
+        if reloaded || tracker.is_added() {
+            *player.properties_mut() = properties.clone();
+        }
+    }
+}
+
+fn save_preset(
+    presets: &PlayerPresets,
+    name: &str,
+    properties: &PlayerProperties,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let mut presets = PlayerPresets {
+        default: presets.default.clone(),
+        presets: presets.presets.clone(),
+    };
+    presets.presets.insert(name.to_owned(), properties.clone());
+    let contents = ron::ser::to_string_pretty(&presets, ron::ser::PrettyConfig::new())?;
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+fn show_player_properties_window(
+    keys: Res<Input<KeyCode>>,
+    mut egui: ResMut<bevy_egui::EguiContext>,
+    mut settings: ResMut<PlayerPresetSettings>,
+    presets: Res<Assets<PlayerPresets>>,
+    asset_settings: Option<Res<AssetServerSettings>>,
+    mut players: Query<&mut Player>,
+) {
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) && keys.just_pressed(KeyCode::I) {
+        settings.window_open = !settings.window_open;
+    }
+    if !settings.window_open {
+        return;
+    }
+    let mut player = if let Ok(player) = players.get_single_mut() {
+        player
+    } else {
+        return;
+    };
+
+    let settings = &mut *settings;
+    let mut open = true;
+    egui::Window::new("Player properties")
+        .open(&mut open)
+        .show(egui.ctx_mut(), |ui| {
+            if let Some(presets) = presets.get(&settings.presets) {
+                let current = settings
+                    .current
+                    .clone()
+                    .unwrap_or_else(|| presets.default.clone());
+                egui::ComboBox::from_label("Preset")
+                    .selected_text(&current)
+                    .show_ui(ui, |ui| {
+                        for (name, properties) in presets.presets.iter() {
+                            if ui.selectable_label(*name == current, name).clicked() {
+                                settings.current = Some(name.clone());
+                                settings.preset_name = name.clone();
+                                *player.properties_mut() = properties.clone();
+                            }
+                        }
+                    });
+
+                ui.separator();
+                player.properties_mut().show_ui(ui);
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut settings.preset_name);
+                    if ui.button("Save preset").clicked() && !settings.preset_name.is_empty() {
+                        let asset_folder = asset_settings
+                            .as_ref()
+                            .map_or("assets", |settings| settings.asset_folder.as_str());
+                        let path = Path::new(asset_folder).join(PLAYER_PRESETS);
+                        let name = settings.preset_name.clone();
+                        settings.status = Some(
+                            match save_preset(presets, &name, player.properties(), &path) {
+                                Ok(()) => {
+                                    settings.current = Some(name.clone());
+                                    format!("Saved \"{}\" to {}", name, path.display())
+                                }
+                                Err(err) => format!("Could not save: {}", err),
+                            },
+                        );
+                    }
+                });
+                if let Some(status) = &settings.status {
+                    ui.label(status);
+                }
+            } else {
+                player.properties_mut().show_ui(ui);
+            }
+        });
+    settings.window_open = open;
+}
@@ -0,0 +1,46 @@
+//! Tile animations defined through LDtk tileset custom data.
+//!
+//! To animate a tile, select it in the LDtk tileset editor and set its custom data to a RON
+//! [`TileAnimation`], e.g. `(frames: [31, 32, 33, 34], frame_duration_ms: 150)`. Every instance of
+//! that tile in the world will then cycle through the given tile IDs.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::{MapQuery, Tile, TileParent, TilePos};
+use serde::Deserialize;
+
+use crate::time::GameplayTime;
+
+#[derive(Component, Deserialize, Clone, Debug)]
+pub struct TileAnimation {
+    /// Tile IDs within the tileset to display, in order.
+    pub frames: Vec<u16>,
+    pub frame_duration_ms: u64,
+}
+
+impl TileAnimation {
+    /// Returns the tile ID to display after the given amount of gameplay time.
+    pub fn frame_at(&self, elapsed: std::time::Duration) -> Option<u16> {
+        if self.frames.is_empty() || self.frame_duration_ms == 0 {
+            return None;
+        }
+
+        let frame = elapsed.as_millis() / self.frame_duration_ms as u128;
+        Some(self.frames[frame as usize % self.frames.len()])
+    }
+}
+
+/// Advances tile animations. Uses [`GameplayTime`] so that tiles freeze while paused.
+pub fn animate_tiles(
+    gameplay_time: Res<GameplayTime>,
+    mut tiles: Query<(&TileAnimation, &mut Tile, &TilePos, &TileParent)>,
+    mut map_query: MapQuery,
+) {
+    for (animation, mut tile, pos, parent) in tiles.iter_mut() {
+        if let Some(texture_index) = animation.frame_at(gameplay_time.elapsed()) {
+            if tile.texture_index != texture_index {
+                tile.texture_index = texture_index;
+                map_query.notify_chunk_for_tile(*pos, parent.map_id, parent.layer_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,1015 @@
+//! Loading of Tiled maps (`.tmx` and `.tmj`) and worlds (`.world`).
+//!
+//! A map is loaded as a world with a single level named after the map file, while a `.world` file
+//! lays out several maps, each of them becoming a level. Maps are converted as follows:
+//! - Tile layers are drawn with their tilesets. Those named `Collision` or `Platforms` also work as
+//!   IntGrid layers, where every non-empty cell is set.
+//! - Objects become entities, identified by their class, or by their name if they have none. Their
+//!   custom properties become entity fields, with object properties becoming entity references.
+//! - Custom properties of the map become level fields, and its background colour is used as the
+//!   level one.
+//!
+//! Only finite maps whose tile layers use the CSV format are supported, along with tilesets made
+//! from a single image without margins or spacing.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use bevy::asset::{Asset, AssetLoader, AssetPath, HandleId, LoadContext, LoadedAsset};
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use crate::{
+    level::{
+        EntityInstance, Level, LevelLayer, TileInstance, Tileset, COLLISION_LAYER, PLATFORM_LAYER,
+    },
+    tile_animation::TileAnimation,
+    world::{WorldProject, WorldSource},
+};
+
+/// Size of the world grid cells used for room tracking, in pixels. Tiled has no equivalent, so
+/// LDtk's default is used.
+const WORLD_GRID_SIZE: i64 = 256;
+
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+const GID_FLAGS: u32 = FLIPPED_HORIZONTALLY_FLAG
+    | FLIPPED_VERTICALLY_FLAG
+    | FLIPPED_DIAGONALLY_FLAG
+    | ROTATED_HEXAGONAL_120_FLAG;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledMap {
+    width: i64,
+    height: i64,
+    #[serde(rename = "tilewidth")]
+    tile_width: i64,
+    #[serde(rename = "tileheight")]
+    tile_height: i64,
+    infinite: bool,
+    #[serde(rename = "backgroundcolor")]
+    background_color: Option<String>,
+    properties: Vec<TiledProperty>,
+    tilesets: Vec<TiledTileset>,
+    layers: Vec<TiledLayer>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledProperty {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledTileset {
+    #[serde(rename = "firstgid")]
+    first_gid: u32,
+    /// Path of the file the tileset is defined in, for tilesets that aren't embedded in the map.
+    source: Option<String>,
+    name: String,
+    #[serde(rename = "tilewidth")]
+    tile_width: i64,
+    image: Option<String>,
+    #[serde(rename = "imagewidth")]
+    image_width: i64,
+    #[serde(rename = "imageheight")]
+    image_height: i64,
+    margin: i64,
+    spacing: i64,
+    tiles: Vec<TiledTile>,
+    /// Folder the paths of the tileset are relative to.
+    #[serde(skip)]
+    base_path: PathBuf,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledTile {
+    id: u32,
+    animation: Vec<TiledFrame>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledFrame {
+    #[serde(rename = "tileid")]
+    tile_id: u32,
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct TiledLayer {
+    /// One of `tilelayer`, `objectgroup`, `imagelayer` or `group`.
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    /// Tile GIDs, as an array for the CSV format or as a string for the Base64 ones.
+    data: serde_json::Value,
+    #[serde(rename = "offsetx")]
+    offset_x: f32,
+    #[serde(rename = "offsety")]
+    offset_y: f32,
+    opacity: f32,
+    visible: bool,
+    #[serde(rename = "parallaxx")]
+    parallax_x: f32,
+    #[serde(rename = "parallaxy")]
+    parallax_y: f32,
+    objects: Vec<TiledObject>,
+    layers: Vec<TiledLayer>,
+}
+
+impl Default for TiledLayer {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            name: String::new(),
+            data: serde_json::Value::Null,
+            offset_x: 0.,
+            offset_y: 0.,
+            opacity: 1.,
+            visible: true,
+            parallax_x: 1.,
+            parallax_y: 1.,
+            objects: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl TiledLayer {
+    fn gids(&self) -> anyhow::Result<Vec<u32>> {
+        self.data
+            .as_array()
+            .and_then(|data| {
+                data.iter()
+                    .map(|gid| gid.as_u64().map(|gid| gid as u32))
+                    .collect()
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Tile layer '{}' must use the CSV tile layer format",
+                    self.name
+                )
+            })
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TiledObject {
+    id: u32,
+    name: String,
+    /// Called `type` before Tiled 1.9.
+    #[serde(rename = "type")]
+    kind: String,
+    class: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// Set for tile objects, whose position is their bottom left corner instead of the top left one.
+    gid: Option<u32>,
+    point: bool,
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledWorld {
+    maps: Vec<TiledWorldMap>,
+}
+
+#[derive(Deserialize)]
+struct TiledWorldMap {
+    #[serde(rename = "fileName")]
+    file_name: String,
+    x: i64,
+    y: i64,
+}
+
+fn parse_map(bytes: &[u8], path: &Path) -> anyhow::Result<TiledMap> {
+    let mut map = if path
+        .extension()
+        .map_or(false, |extension| extension == "tmx")
+    {
+        xml::parse_map(std::str::from_utf8(bytes)?)?
+    } else {
+        serde_json::from_slice(bytes)?
+    };
+
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+    for tileset in map.tilesets.iter_mut() {
+        tileset.base_path = base_path.to_owned();
+    }
+
+    Ok(map)
+}
+
+/// Parses a tileset defined in its own file, keeping the first GID it is used with.
+fn parse_tileset(bytes: &[u8], path: &Path, first_gid: u32) -> anyhow::Result<TiledTileset> {
+    let tileset = if path
+        .extension()
+        .map_or(false, |extension| extension == "tsx")
+    {
+        xml::parse_tileset(std::str::from_utf8(bytes)?)?
+    } else {
+        serde_json::from_slice(bytes)?
+    };
+
+    Ok(TiledTileset {
+        first_gid,
+        base_path: path.parent().unwrap_or_else(|| Path::new("")).to_owned(),
+        ..tileset
+    })
+}
+
+/// Returns the path of the external tilesets of a map, along with their index in the map.
+fn external_tilesets(map: &TiledMap) -> Vec<(usize, PathBuf)> {
+    map.tilesets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, tileset)| {
+            tileset
+                .source
+                .as_ref()
+                .map(|source| (i, tileset.base_path.join(source)))
+        })
+        .collect()
+}
+
+/// Reads a map and its external tilesets through the asset server. Returns the paths of the
+/// tilesets along with the map.
+async fn read_map(
+    load_context: &LoadContext<'_>,
+    path: &Path,
+) -> anyhow::Result<(TiledMap, Vec<PathBuf>)> {
+    let mut map = parse_map(&load_context.read_asset_bytes(path).await?, path)?;
+    let mut tileset_paths = Vec::new();
+    for (i, tileset_path) in external_tilesets(&map) {
+        let bytes = load_context.read_asset_bytes(&tileset_path).await?;
+        map.tilesets[i] = parse_tileset(&bytes, &tileset_path, map.tilesets[i].first_gid)?;
+        tileset_paths.push(tileset_path);
+    }
+    Ok((map, tileset_paths))
+}
+
+/// Reads a map and its external tilesets straight from disk.
+fn read_map_from_file(path: &Path) -> anyhow::Result<TiledMap> {
+    let mut map = parse_map(&std::fs::read(path)?, path)?;
+    for (i, tileset_path) in external_tilesets(&map) {
+        let bytes = std::fs::read(&tileset_path)?;
+        map.tilesets[i] = parse_tileset(&bytes, &tileset_path, map.tilesets[i].first_gid)?;
+    }
+    Ok(map)
+}
+
+/// Returns the identifier of the level made from the map at the given path.
+fn level_identifier(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns the IID of the entity made from an object. Object IDs are only unique within their map,
+/// so they are prefixed with the level identifier.
+fn object_iid(level: &str, id: u64) -> String {
+    format!("{}:{}", level, id)
+}
+
+/// Converts a Tiled colour, `#RRGGBB` or `#AARRGGBB`, to a hex code with the alpha at the end.
+fn convert_color(color: &str) -> String {
+    let hex = color.trim_start_matches('#');
+    if hex.len() == 8 {
+        format!("{}{}", &hex[2..], &hex[..2])
+    } else {
+        hex.to_owned()
+    }
+}
+
+fn convert_properties(
+    properties: &[TiledProperty],
+    level: &str,
+) -> HashMap<String, serde_json::Value> {
+    properties
+        .iter()
+        .filter_map(|property| {
+            let value = match property.kind.as_str() {
+                // Object properties are 0 when unset
+                "object" => property
+                    .value
+                    .as_u64()
+                    .filter(|id| *id != 0)
+                    .map(|id| serde_json::json!({ "entityIid": object_iid(level, id) }))?,
+                "color" => serde_json::Value::String(convert_color(property.value.as_str()?)),
+                _ => property.value.clone(),
+            };
+            Some((property.name.clone(), value))
+        })
+        .collect()
+}
+
+fn convert_object(
+    object: &TiledObject,
+    level: &str,
+    map: &TiledMap,
+    offset: Vec2,
+) -> EntityInstance {
+    let (width, height, pivot) = if object.point || (object.width == 0. && object.height == 0.) {
+        // Points take up a tile around them
+        (map.tile_width, map.tile_height, vec2(0.5, 0.5))
+    } else if object.gid.is_some() {
+        (object.width as i64, object.height as i64, vec2(0., 1.))
+    } else {
+        (object.width as i64, object.height as i64, Vec2::ZERO)
+    };
+
+    EntityInstance {
+        identifier: [&object.class, &object.kind, &object.name]
+            .into_iter()
+            .find(|identifier| !identifier.is_empty())
+            .cloned()
+            .unwrap_or_default(),
+        iid: object_iid(level, object.id as u64),
+        px: [
+            (object.x + offset.x).round() as i64,
+            (object.y + offset.y).round() as i64,
+        ],
+        width,
+        height,
+        pivot,
+        fields: convert_properties(&object.properties, level),
+    }
+}
+
+/// Properties group layers pass on to their children.
+#[derive(Clone, Copy)]
+struct InheritedLayerProperties {
+    offset: Vec2,
+    opacity: f32,
+    visible: bool,
+    parallax: Vec2,
+}
+
+impl InheritedLayerProperties {
+    fn apply(self, layer: &TiledLayer) -> Self {
+        Self {
+            offset: self.offset + vec2(layer.offset_x, layer.offset_y),
+            opacity: self.opacity * layer.opacity,
+            visible: self.visible && layer.visible,
+            parallax: self.parallax * vec2(layer.parallax_x, layer.parallax_y),
+        }
+    }
+}
+
+/// Lists the layers of a map in order, replacing group layers with their contents.
+fn flatten_layers<'a>(
+    layers: &'a [TiledLayer],
+    inherited: InheritedLayerProperties,
+    output: &mut Vec<(&'a TiledLayer, InheritedLayerProperties)>,
+) {
+    for layer in layers.iter() {
+        let properties = inherited.apply(layer);
+        if layer.kind == "group" {
+            flatten_layers(&layer.layers, properties, output);
+        } else {
+            output.push((layer, properties));
+        }
+    }
+}
+
+/// Accumulates the maps of a world, sharing tilesets between them.
+#[derive(Default)]
+struct WorldBuilder {
+    levels: Vec<Level>,
+    tilesets: HashMap<i64, Tileset>,
+    /// UIDs of the tilesets added, by the path of their image.
+    tileset_uids: HashMap<PathBuf, i64>,
+    /// Paths of the tileset images, relative to the assets folder or the working directory.
+    tileset_images: Vec<(i64, PathBuf)>,
+    tile_animations: HashMap<(i64, u16), TileAnimation>,
+}
+
+impl WorldBuilder {
+    fn add_tileset(&mut self, tileset: &TiledTileset) -> i64 {
+        let image_path = tileset
+            .image
+            .as_ref()
+            .map(|image| tileset.base_path.join(image));
+        let key = image_path
+            .clone()
+            .unwrap_or_else(|| tileset.base_path.join(&tileset.name));
+        if let Some(&uid) = self.tileset_uids.get(&key) {
+            return uid;
+        }
+
+        let uid = self.tileset_uids.len() as i64;
+        self.tileset_uids.insert(key, uid);
+
+        if tileset.margin != 0 || tileset.spacing != 0 {
+            warn!(
+                "Tileset '{}' uses margins or spacing, which are not supported",
+                tileset.name
+            );
+        }
+
+        self.tilesets.insert(
+            uid,
+            Tileset {
+                identifier: tileset.name.clone(),
+                tile_size: tileset.tile_width,
+                px_wid: tileset.image_width,
+                px_hei: tileset.image_height,
+                spacing: tileset.spacing,
+                padding: tileset.margin,
+                texture: None,
+            },
+        );
+        if let Some(image_path) = image_path {
+            self.tileset_images.push((uid, image_path));
+        }
+
+        for tile in tileset
+            .tiles
+            .iter()
+            .filter(|tile| !tile.animation.is_empty())
+        {
+            // Frames can't have different durations here, so the first one is used for all
+            self.tile_animations.insert(
+                (uid, tile.id as u16),
+                TileAnimation {
+                    frames: tile
+                        .animation
+                        .iter()
+                        .map(|frame| frame.tile_id as u16)
+                        .collect(),
+                    frame_duration_ms: tile.animation[0].duration,
+                },
+            );
+        }
+
+        uid
+    }
+
+    /// Adds a map as a level whose top left corner is at the given position, in pixels.
+    fn add_map(
+        &mut self,
+        map: &TiledMap,
+        identifier: String,
+        (world_x, world_y): (i64, i64),
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(!map.infinite, "Map '{}' is infinite", identifier);
+
+        let mut tilesets: Vec<(u32, i64)> = map
+            .tilesets
+            .iter()
+            .map(|tileset| (tileset.first_gid, self.add_tileset(tileset)))
+            .collect();
+        tilesets.sort_unstable_by_key(|(first_gid, _)| *first_gid);
+        // Returns the UID of the tileset a GID belongs to and the ID of the tile in it
+        let find_tile = |gid: u32| {
+            tilesets
+                .iter()
+                .rev()
+                .find(|(first_gid, _)| *first_gid <= gid)
+                .map(|(first_gid, uid)| (*uid, (gid - first_gid) as u16))
+        };
+
+        let mut flattened_layers = Vec::new();
+        flatten_layers(
+            &map.layers,
+            InheritedLayerProperties {
+                offset: Vec2::ZERO,
+                opacity: 1.,
+                visible: true,
+                parallax: Vec2::ONE,
+            },
+            &mut flattened_layers,
+        );
+
+        let mut layers = Vec::new();
+        let mut entities = Vec::new();
+        for (layer, properties) in flattened_layers {
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    let gids = layer.gids()?;
+                    anyhow::ensure!(
+                        gids.len() as i64 == map.width * map.height,
+                        "Tile layer '{}' of map '{}' has {} tiles, expected {}",
+                        layer.name,
+                        identifier,
+                        gids.len(),
+                        map.width * map.height
+                    );
+
+                    // Layers only use one tileset here, so split the layer by tileset
+                    let mut tiles_by_tileset: Vec<(Option<i64>, Vec<TileInstance>)> = Vec::new();
+                    for (i, &gid) in gids.iter().enumerate() {
+                        let (tileset_uid, id) = match find_tile(gid & !GID_FLAGS) {
+                            Some(tile) if properties.visible && gid & !GID_FLAGS != 0 => tile,
+                            _ => continue,
+                        };
+                        let tile = TileInstance {
+                            px: [
+                                i as i64 % map.width * map.tile_width,
+                                i as i64 / map.width * map.tile_height,
+                            ],
+                            id,
+                            flip_x: gid & FLIPPED_HORIZONTALLY_FLAG != 0,
+                            flip_y: gid & FLIPPED_VERTICALLY_FLAG != 0,
+                            flip_d: gid & FLIPPED_DIAGONALLY_FLAG != 0,
+                        };
+                        match tiles_by_tileset
+                            .iter_mut()
+                            .find(|(uid, _)| *uid == Some(tileset_uid))
+                        {
+                            Some((_, tiles)) => tiles.push(tile),
+                            None => tiles_by_tileset.push((Some(tileset_uid), vec![tile])),
+                        }
+                    }
+
+                    let mut int_grid = [COLLISION_LAYER, PLATFORM_LAYER]
+                        .contains(&layer.name.as_str())
+                        .then(|| {
+                            gids.iter()
+                                .map(|gid| (gid & !GID_FLAGS != 0) as i64)
+                                .collect()
+                        });
+                    if tiles_by_tileset.is_empty() && int_grid.is_some() {
+                        tiles_by_tileset.push((None, Vec::new()));
+                    }
+
+                    for (tileset_uid, tiles) in tiles_by_tileset {
+                        layers.push(LevelLayer {
+                            identifier: layer.name.clone(),
+                            grid_size: map.tile_width,
+                            c_wid: map.width,
+                            c_hei: map.height,
+                            px_offset_x: properties.offset.x.round() as i64,
+                            px_offset_y: properties.offset.y.round() as i64,
+                            opacity: properties.opacity,
+                            // Tiled factors are how fast a layer scrolls compared to the camera
+                            parallax_factor: Vec2::ONE - properties.parallax,
+                            int_grid: int_grid.take(),
+                            tileset_uid,
+                            tiles,
+                        });
+                    }
+                }
+                "objectgroup" => entities.extend(
+                    layer
+                        .objects
+                        .iter()
+                        .map(|object| convert_object(object, &identifier, map, properties.offset)),
+                ),
+                _ => (),
+            }
+        }
+        // Tiled lists layers from bottom to top
+        layers.reverse();
+
+        self.levels.push(Level {
+            uid: self.levels.len() as i64,
+            world_x,
+            world_y,
+            px_wid: map.width * map.tile_width,
+            px_hei: map.height * map.tile_height,
+            bg_color: map.background_color.as_deref().map(convert_color),
+            fields: convert_properties(&map.properties, &identifier),
+            layers: Some(layers),
+            entities,
+            identifier,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the world along with the paths of its tileset images, whose textures are left for the
+    /// caller to set.
+    fn build(self) -> (WorldProject, Vec<(i64, PathBuf)>) {
+        (
+            WorldProject {
+                levels: self.levels,
+                tilesets: self.tilesets,
+                tile_animations: self.tile_animations,
+                world_grid: Some((WORLD_GRID_SIZE, WORLD_GRID_SIZE)),
+                entity_definitions: None,
+                source: WorldSource::Tiled(TiledSource::default()),
+            },
+            self.tileset_images,
+        )
+    }
+}
+
+/// Data of a Tiled world needed to hot-reload it.
+#[derive(Default)]
+pub struct TiledSource {
+    /// Maps or external tilesets the world was read from, besides its own file. Their contents are
+    /// already part of the world, the handles are only kept to reload it when they are modified.
+    pub files: Vec<HandleUntyped>,
+}
+
+/// An external tileset (`.tsx` or `.tsj`). Maps read their tilesets on their own, this asset is
+/// only loaded to find out when the file is modified.
+#[derive(TypeUuid)]
+#[uuid = "6f1b3c52-94d7-4e0a-b8a1-2c7e5d9f0a43"]
+pub struct TiledTilesetFile;
+
+pub struct TiledTilesetLoader;
+
+impl AssetLoader for TiledTilesetLoader {
+    fn load<'a>(
+        &'a self,
+        _bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(TiledTilesetFile));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tsx", "tsj"]
+    }
+}
+
+/// Sets the textures of the tilesets of a world and makes it the loaded asset. `files` are the
+/// other files the world was read from, loaded as `T` so that modifying them reloads the world.
+fn set_world_asset<T: Asset>(
+    load_context: &mut LoadContext,
+    builder: WorldBuilder,
+    files: Vec<PathBuf>,
+) {
+    let (mut world, images) = builder.build();
+    let mut dependencies: Vec<AssetPath> = images
+        .into_iter()
+        .map(|(uid, path)| {
+            let path: AssetPath = path.into();
+            if let Some(tileset) = world.tilesets.get_mut(&uid) {
+                tileset.texture = Some(load_context.get_handle(path.clone()));
+            }
+            path
+        })
+        .collect();
+
+    let mut source = TiledSource::default();
+    for path in files {
+        let path: AssetPath = path.into();
+        source.files.push(
+            load_context
+                .get_handle::<_, T>(path.clone())
+                .clone_untyped(),
+        );
+        dependencies.push(path);
+    }
+    world.source = WorldSource::Tiled(source);
+
+    load_context.set_default_asset(LoadedAsset::new(world).with_dependencies(dependencies));
+}
+
+pub struct TiledMapLoader;
+
+impl AssetLoader for TiledMapLoader {
+    fn load<'a>(
+        &'a self,
+        _bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_owned();
+            let (map, tileset_paths) = read_map(load_context, &path).await?;
+
+            let mut builder = WorldBuilder::default();
+            builder.add_map(&map, level_identifier(&path), (0, 0))?;
+            set_world_asset::<TiledTilesetFile>(load_context, builder, tileset_paths);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmx", "tmj"]
+    }
+}
+
+pub struct TiledWorldLoader;
+
+impl AssetLoader for TiledWorldLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let world: TiledWorld = serde_json::from_slice(bytes)?;
+            let base_path = load_context.path().parent().unwrap().to_owned();
+
+            let mut builder = WorldBuilder::default();
+            let mut map_paths = Vec::new();
+            for entry in world.maps.iter() {
+                let path = base_path.join(&entry.file_name);
+                let (map, _) = read_map(load_context, &path).await?;
+                builder.add_map(&map, level_identifier(&path), (entry.x, entry.y))?;
+                map_paths.push(path);
+            }
+            // The maps are loaded as worlds of their own, which reload when their tilesets change
+            set_world_asset::<WorldProject>(load_context, builder, map_paths);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["world"]
+    }
+}
+
+/// Reloads Tiled worlds when one of the maps or tilesets they were read from is modified, since the
+/// asset server only watches the file of the world itself.
+pub fn reload_tiled_worlds(
+    asset_server: Res<AssetServer>,
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    mut tileset_events: EventReader<AssetEvent<TiledTilesetFile>>,
+    maps: Res<Assets<WorldProject>>,
+) {
+    let modified: Vec<HandleId> = map_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.id),
+            _ => None,
+        })
+        .chain(tileset_events.iter().filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.id),
+            _ => None,
+        }))
+        .collect();
+    if modified.is_empty() {
+        return;
+    }
+
+    for (id, map) in maps.iter() {
+        let reload = match &map.source {
+            WorldSource::Tiled(source) => {
+                source.files.iter().any(|file| modified.contains(&file.id))
+            }
+            _ => false,
+        };
+        if let (true, Some(path)) = (reload, asset_server.get_handle_path(id)) {
+            info!("Reloading Tiled world '{}'", path.path().display());
+            asset_server.reload_asset(path);
+        }
+    }
+}
+
+/// Reads a map or world straight from disk, without an asset server.
+///
+/// Tilesets get a placeholder texture if their image exists, so the world can still be validated.
+pub fn load_from_file(path: &Path) -> anyhow::Result<WorldProject> {
+    let mut builder = WorldBuilder::default();
+    if path
+        .extension()
+        .map_or(false, |extension| extension == "world")
+    {
+        let world: TiledWorld = serde_json::from_slice(&std::fs::read(path)?)?;
+        let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+        for entry in world.maps.iter() {
+            let map_path = base_path.join(&entry.file_name);
+            let map = read_map_from_file(&map_path)?;
+            builder.add_map(&map, level_identifier(&map_path), (entry.x, entry.y))?;
+        }
+    } else {
+        let map = read_map_from_file(path)?;
+        builder.add_map(&map, level_identifier(path), (0, 0))?;
+    }
+
+    let (mut world, images) = builder.build();
+    for (uid, image_path) in images {
+        if let (true, Some(tileset)) = (image_path.exists(), world.tilesets.get_mut(&uid)) {
+            tileset.texture = Some(Handle::default());
+        }
+    }
+
+    Ok(world)
+}
+
+/// Parsing of the XML formats, into the same structures the JSON formats deserialize to.
+mod xml {
+    use std::str::FromStr;
+
+    use roxmltree::Node;
+
+    use super::*;
+
+    fn attribute<T: FromStr>(node: Node, name: &str) -> Option<T> {
+        node.attribute(name)?.parse().ok()
+    }
+
+    fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+        node.children().find(|child| child.has_tag_name(name))
+    }
+
+    fn parse_properties(node: Node) -> Vec<TiledProperty> {
+        let properties = if let Some(properties) = child(node, "properties") {
+            properties
+        } else {
+            return Vec::new();
+        };
+
+        properties
+            .children()
+            .filter(|property| property.has_tag_name("property"))
+            .map(|property| {
+                let kind = property.attribute("type").unwrap_or("string").to_owned();
+                // Multiline strings are stored as text instead of in the value attribute
+                let text = property
+                    .attribute("value")
+                    .or_else(|| property.text())
+                    .unwrap_or_default();
+                let value = match kind.as_str() {
+                    "bool" => serde_json::Value::Bool(text == "true"),
+                    "int" | "object" => text
+                        .parse::<i64>()
+                        .map_or(serde_json::Value::Null, Into::into),
+                    "float" => text
+                        .parse::<f64>()
+                        .map_or(serde_json::Value::Null, Into::into),
+                    "class" => serde_json::Value::Object(
+                        parse_properties(property)
+                            .into_iter()
+                            .map(|property| (property.name, property.value))
+                            .collect(),
+                    ),
+                    _ => serde_json::Value::String(text.to_owned()),
+                };
+
+                TiledProperty {
+                    name: property.attribute("name").unwrap_or_default().to_owned(),
+                    kind,
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    fn parse_tileset_node(node: Node) -> TiledTileset {
+        let image = child(node, "image");
+        TiledTileset {
+            first_gid: attribute(node, "firstgid").unwrap_or(1),
+            source: node.attribute("source").map(str::to_owned),
+            name: node.attribute("name").unwrap_or_default().to_owned(),
+            tile_width: attribute(node, "tilewidth").unwrap_or(0),
+            image: image.and_then(|image| image.attribute("source").map(str::to_owned)),
+            image_width: image
+                .and_then(|image| attribute(image, "width"))
+                .unwrap_or(0),
+            image_height: image
+                .and_then(|image| attribute(image, "height"))
+                .unwrap_or(0),
+            margin: attribute(node, "margin").unwrap_or(0),
+            spacing: attribute(node, "spacing").unwrap_or(0),
+            tiles: node
+                .children()
+                .filter(|tile| tile.has_tag_name("tile"))
+                .map(|tile| TiledTile {
+                    id: attribute(tile, "id").unwrap_or(0),
+                    animation: child(tile, "animation")
+                        .into_iter()
+                        .flat_map(|animation| animation.children())
+                        .filter(|frame| frame.has_tag_name("frame"))
+                        .map(|frame| TiledFrame {
+                            tile_id: attribute(frame, "tileid").unwrap_or(0),
+                            duration: attribute(frame, "duration").unwrap_or(0),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            base_path: PathBuf::new(),
+        }
+    }
+
+    fn parse_layer_data(node: Node, name: &str) -> anyhow::Result<serde_json::Value> {
+        let data = if let Some(data) = child(node, "data") {
+            data
+        } else {
+            return Ok(serde_json::Value::Null);
+        };
+
+        let gids: Vec<serde_json::Value> = match data.attribute("encoding") {
+            Some("csv") => data
+                .text()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|gid| !gid.is_empty())
+                .map(|gid| Ok(gid.parse::<u32>()?.into()))
+                .collect::<anyhow::Result<_>>()?,
+            // Deprecated format with an element per tile
+            None => data
+                .children()
+                .filter(|tile| tile.has_tag_name("tile"))
+                .map(|tile| attribute::<u32>(tile, "gid").unwrap_or(0).into())
+                .collect(),
+            Some(_) => anyhow::bail!("Tile layer '{}' must use the CSV tile layer format", name),
+        };
+
+        Ok(serde_json::Value::Array(gids))
+    }
+
+    fn parse_object(node: Node) -> TiledObject {
+        TiledObject {
+            id: attribute(node, "id").unwrap_or(0),
+            name: node.attribute("name").unwrap_or_default().to_owned(),
+            kind: node.attribute("type").unwrap_or_default().to_owned(),
+            class: node.attribute("class").unwrap_or_default().to_owned(),
+            x: attribute(node, "x").unwrap_or(0.),
+            y: attribute(node, "y").unwrap_or(0.),
+            width: attribute(node, "width").unwrap_or(0.),
+            height: attribute(node, "height").unwrap_or(0.),
+            gid: attribute(node, "gid"),
+            point: child(node, "point").is_some(),
+            properties: parse_properties(node),
+        }
+    }
+
+    fn parse_layers(node: Node) -> anyhow::Result<Vec<TiledLayer>> {
+        node.children()
+            .filter_map(|layer| {
+                let kind = match layer.tag_name().name() {
+                    "layer" => "tilelayer",
+                    "objectgroup" => "objectgroup",
+                    "imagelayer" => "imagelayer",
+                    "group" => "group",
+                    _ => return None,
+                };
+                let name = layer.attribute("name").unwrap_or_default().to_owned();
+                let default = TiledLayer::default();
+
+                Some(Ok(TiledLayer {
+                    kind: kind.to_owned(),
+                    data: match parse_layer_data(layer, &name) {
+                        Ok(data) => data,
+                        Err(err) => return Some(Err(err)),
+                    },
+                    offset_x: attribute(layer, "offsetx").unwrap_or(default.offset_x),
+                    offset_y: attribute(layer, "offsety").unwrap_or(default.offset_y),
+                    opacity: attribute(layer, "opacity").unwrap_or(default.opacity),
+                    visible: layer
+                        .attribute("visible")
+                        .map_or(true, |visible| visible != "0"),
+                    parallax_x: attribute(layer, "parallaxx").unwrap_or(default.parallax_x),
+                    parallax_y: attribute(layer, "parallaxy").unwrap_or(default.parallax_y),
+                    objects: layer
+                        .children()
+                        .filter(|object| object.has_tag_name("object"))
+                        .map(parse_object)
+                        .collect(),
+                    layers: match parse_layers(layer) {
+                        Ok(layers) => layers,
+                        Err(err) => return Some(Err(err)),
+                    },
+                    name,
+                }))
+            })
+            .collect()
+    }
+
+    pub(super) fn parse_map(text: &str) -> anyhow::Result<TiledMap> {
+        let document = roxmltree::Document::parse(text)?;
+        let map = document.root_element();
+        anyhow::ensure!(map.has_tag_name("map"), "Not a Tiled map");
+
+        Ok(TiledMap {
+            width: attribute(map, "width").unwrap_or(0),
+            height: attribute(map, "height").unwrap_or(0),
+            tile_width: attribute(map, "tilewidth").unwrap_or(0),
+            tile_height: attribute(map, "tileheight").unwrap_or(0),
+            infinite: map.attribute("infinite") == Some("1"),
+            background_color: map.attribute("backgroundcolor").map(str::to_owned),
+            properties: parse_properties(map),
+            tilesets: map
+                .children()
+                .filter(|tileset| tileset.has_tag_name("tileset"))
+                .map(parse_tileset_node)
+                .collect(),
+            layers: parse_layers(map)?,
+        })
+    }
+
+    pub(super) fn parse_tileset(text: &str) -> anyhow::Result<TiledTileset> {
+        let document = roxmltree::Document::parse(text)?;
+        let tileset = document.root_element();
+        anyhow::ensure!(tileset.has_tag_name("tileset"), "Not a Tiled tileset");
+
+        Ok(parse_tileset_node(tileset))
+    }
+}
@@ -0,0 +1,304 @@
+//! Metroidvania-style transitions played when the player walks into another level: physics
+//! freeze for a moment while the camera pans to the new room, optionally fading to black.
+//!
+//! The transition into a level is configured through the following LDtk level fields:
+//! - `Transition_Duration` (Float): Seconds the camera takes to pan into the level. Set to 0 to snap
+//!   the camera immediately.
+//! - `Transition_Freeze` (Float): Seconds physics stay frozen for. Defaults to the pan duration.
+//! - `Transition_Fade` (Bool): Whether to fade out and back in during the pan.
+//!
+//! Other transitions, such as the ones played by warps, can be started with [`PlayTransition`].
+
+use std::time::Duration;
+
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+use crate::{
+    follow::{BoundsTransition, CameraFollow},
+    level::Level,
+    physics::PhysicsFrozen,
+    player::{RoomEntered, TeleportPlayer},
+    world::GameWorld,
+    worlds::SwitchWorld,
+    AppState, Player, WorldProject,
+};
+
+const DEFAULT_TRANSITION_DURATION: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoomTransitionSettings {
+    pub duration: Duration,
+    pub freeze: Duration,
+    pub fade: bool,
+}
+
+impl Default for RoomTransitionSettings {
+    fn default() -> Self {
+        Self {
+            duration: DEFAULT_TRANSITION_DURATION,
+            freeze: DEFAULT_TRANSITION_DURATION,
+            fade: false,
+        }
+    }
+}
+
+impl RoomTransitionSettings {
+    /// Reads the transition settings of a level from its fields, using the defaults for those not
+    /// set.
+    pub fn from_level(level: &Level) -> Self {
+        let seconds = |identifier| {
+            level
+                .field(identifier)
+                .and_then(|value| value.as_f64())
+                .map(|seconds| Duration::from_secs_f64(seconds.max(0.)))
+        };
+
+        let duration = seconds("Transition_Duration").unwrap_or(DEFAULT_TRANSITION_DURATION);
+        Self {
+            duration,
+            freeze: seconds("Transition_Freeze").unwrap_or(duration),
+            fade: level
+                .field("Transition_Fade")
+                .and_then(|value| value.as_bool())
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Plays a transition. Sent automatically when the player walks into another level.
+#[derive(Debug, Clone)]
+pub struct PlayTransition {
+    pub settings: RoomTransitionSettings,
+    /// Position to teleport the player to halfway through the transition, once the screen has
+    /// faded out. The camera doesn't pan during teleports.
+    pub teleport_to: Option<Vec2>,
+    /// World to switch to halfway through the transition. The camera doesn't pan either.
+    pub switch_world: Option<SwitchWorld>,
+}
+
+/// Full-screen sprite used to fade out during transitions. Child of the camera.
+#[derive(Component)]
+struct TransitionFade;
+
+/// Returns the size of the area seen by the camera, in bevy units.
+fn view_size(projection: &OrthographicProjection) -> Vec2 {
+    vec2(
+        projection.right - projection.left,
+        projection.top - projection.bottom,
+    ) * projection.scale
+}
+
+struct ActiveTransition {
+    pan: Timer,
+    freeze: Timer,
+    fade: Option<Entity>,
+    teleport_to: Option<Vec2>,
+    switch_world: Option<SwitchWorld>,
+}
+
+/// The room transition currently being played, if any.
+#[derive(Default)]
+pub struct RoomTransition {
+    active: Option<ActiveTransition>,
+}
+
+impl RoomTransition {
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+}
+
+pub struct RoomTransitionPlugin;
+
+impl Plugin for RoomTransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoomTransition>()
+            .add_event::<PlayTransition>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(play_room_transitions)
+                    .with_system(start_transitions.after(play_room_transitions))
+                    .with_system(update_room_transition.after(start_transitions)),
+            );
+    }
+}
+
+fn play_room_transitions(
+    mut entered_events: EventReader<RoomEntered>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    player: Query<(), With<Player>>,
+    mut transition_events: EventWriter<PlayTransition>,
+) {
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+
+    for event in entered_events.iter() {
+        // No transition is played when the player is first placed in the world, and teleports
+        // play their own
+        if event.from.is_none() || event.teleported || player.get(event.entity).is_err() {
+            continue;
+        }
+
+        if let Some(level) = map.level(&event.room) {
+            transition_events.send(PlayTransition {
+                settings: RoomTransitionSettings::from_level(level),
+                teleport_to: None,
+                switch_world: None,
+            });
+        }
+    }
+}
+
+fn start_transitions(
+    mut commands: Commands,
+    mut transition_events: EventReader<PlayTransition>,
+    mut camera: Query<
+        (
+            Entity,
+            &Transform,
+            &OrthographicProjection,
+            &mut CameraFollow,
+        ),
+        With<Camera>,
+    >,
+    mut transition: ResMut<RoomTransition>,
+    mut frozen: ResMut<PhysicsFrozen>,
+    mut teleport_events: EventWriter<TeleportPlayer>,
+    mut switch_events: EventWriter<SwitchWorld>,
+) {
+    for PlayTransition {
+        settings,
+        teleport_to,
+        switch_world,
+    } in transition_events.iter().cloned()
+    {
+        let (camera_entity, camera_transform, projection, mut follow) =
+            if let Ok(camera) = camera.get_single_mut() {
+                camera
+            } else {
+                continue;
+            };
+
+        // Replace whatever transition was already being played, without dropping its teleport
+        if let Some(active) = transition.active.take() {
+            if let Some(fade) = active.fade {
+                commands.entity(fade).despawn();
+            }
+            if let Some(position) = active.teleport_to {
+                teleport_events.send(TeleportPlayer { position });
+            }
+            if let Some(switch) = active.switch_world {
+                switch_events.send(switch);
+            }
+        }
+
+        follow.transition =
+            (teleport_to.is_none() && !settings.duration.is_zero()).then(|| BoundsTransition {
+                from: camera_transform.translation.truncate(),
+                progress: 0.,
+            });
+        frozen.0 = !settings.freeze.is_zero();
+
+        let fade = (settings.fade && !settings.duration.is_zero()).then(|| {
+            let fade = commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0., 0., 0., 0.),
+                        custom_size: Some(view_size(projection)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., -1.),
+                    ..default()
+                })
+                .insert(TransitionFade)
+                .id();
+            commands.entity(camera_entity).add_child(fade);
+            fade
+        });
+
+        transition.active = Some(ActiveTransition {
+            pan: Timer::new(settings.duration, false),
+            freeze: Timer::new(settings.freeze, false),
+            fade,
+            teleport_to,
+            switch_world,
+        });
+    }
+}
+
+fn update_room_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transition: ResMut<RoomTransition>,
+    mut frozen: ResMut<PhysicsFrozen>,
+    mut camera: Query<(&mut CameraFollow, &OrthographicProjection), With<Camera>>,
+    mut fades: Query<&mut Sprite, With<TransitionFade>>,
+    mut teleport_events: EventWriter<TeleportPlayer>,
+    mut switch_events: EventWriter<SwitchWorld>,
+) {
+    let active = if let Some(active) = &mut transition.active {
+        active
+    } else {
+        return;
+    };
+
+    active.pan.tick(time.delta());
+    active.freeze.tick(time.delta());
+
+    // Ease in and out so the pan doesn't start or stop abruptly
+    let t = if active.pan.duration().is_zero() {
+        1.
+    } else {
+        active.pan.percent()
+    };
+    let progress = t * t * (3. - 2. * t);
+
+    let projection = if let Ok((mut follow, projection)) = camera.get_single_mut() {
+        if let Some(pan) = &mut follow.transition {
+            pan.progress = progress;
+        }
+        Some(projection)
+    } else {
+        None
+    };
+
+    if let Some(mut sprite) = active.fade.and_then(|fade| fades.get_mut(fade).ok()) {
+        // Fully faded out halfway through the pan
+        sprite.color.set_a(1. - (2. * t - 1.).abs());
+        // The camera may zoom out while the transition plays
+        if let Some(projection) = projection {
+            let size = Some(view_size(projection));
+            if sprite.custom_size != size {
+                sprite.custom_size = size;
+            }
+        }
+    }
+
+    if t >= 0.5 {
+        if let Some(position) = active.teleport_to.take() {
+            teleport_events.send(TeleportPlayer { position });
+        }
+        if let Some(switch) = active.switch_world.take() {
+            switch_events.send(switch);
+        }
+    }
+
+    if active.freeze.finished() {
+        frozen.0 = false;
+    }
+
+    if active.pan.finished() && active.freeze.finished() {
+        if let Ok((mut follow, _)) = camera.get_single_mut() {
+            follow.transition = None;
+        }
+        if let Some(fade) = active.fade {
+            commands.entity(fade).despawn();
+        }
+        transition.active = None;
+    }
+}
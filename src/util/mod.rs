@@ -1,3 +1,6 @@
+pub mod duration_ms;
+mod rng;
 mod time;
 
+pub use rng::*;
 pub use time::*;
@@ -0,0 +1,453 @@
+//! Checks performed on worlds once they are loaded, so that authoring mistakes are reported
+//! on an error screen instead of crashing the game.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use bevy::{asset::LoadState, prelude::*};
+use bevy_egui::egui;
+
+use crate::{
+    doors::{entity_refs, DOOR_IDENTIFIER},
+    level::{Level, COLLISION_LAYER, PLATFORM_LAYER},
+    world::{world_changed, GameWorld, LevelTile, TileRect, TILE_SIZE},
+    AppState, WorldProject,
+};
+
+pub const START_POINT_IDENTIFIER: &str = "Start_Point";
+/// Layers read by [`WorldProject::get_tile`], which must use a grid size of [`TILE_SIZE`].
+const COLLISION_LAYERS: [&str; 2] = [COLLISION_LAYER, PLATFORM_LAYER];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldError {
+    /// The world file couldn't be loaded, usually because it isn't valid JSON.
+    LoadFailed {
+        path: String,
+    },
+    /// The project does not use a grid world layout, which room tracking requires.
+    MissingWorldGrid,
+    /// The world grid cells are smaller than a tile, so rooms can't be divided into them.
+    WorldGridTooSmall {
+        width: i64,
+        height: i64,
+    },
+    MissingEntityDefinition {
+        identifier: String,
+    },
+    MissingStartPoint,
+    /// The level has no layer instances, e.g. because its external level file wasn't found.
+    MissingLayers {
+        level: String,
+    },
+    MissingTileset {
+        level: String,
+        layer: String,
+        tileset_uid: i64,
+    },
+    IntGridSizeMismatch {
+        level: String,
+        layer: String,
+        expected: usize,
+        found: usize,
+    },
+    MissingCollisionLayer {
+        level: String,
+    },
+    /// The IntGrid layer doesn't cover the level exactly, i.e. the level size is not a multiple of
+    /// the layer grid size.
+    LayerSizeMismatch {
+        level: String,
+        layer: String,
+    },
+    CollisionGridSizeMismatch {
+        level: String,
+        layer: String,
+        grid_size: i64,
+    },
+    TileGridSizeMismatch {
+        level: String,
+        layer: String,
+        layer_grid_size: i64,
+        tileset_grid_size: i64,
+    },
+    /// The level can't be walked into from the level containing the start point.
+    UnreachableLevel {
+        level: String,
+    },
+    /// A key, lever or pressure plate targets an entity which isn't a door.
+    InvalidDoorTarget {
+        level: String,
+        entity: String,
+        target: String,
+    },
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::LoadFailed { path } => write!(
+                f,
+                "Could not parse world file '{}', see the log for details",
+                path
+            ),
+            WorldError::MissingWorldGrid => {
+                write!(f, "The project must use the GridVania world layout")
+            }
+            WorldError::WorldGridTooSmall { width, height } => write!(
+                f,
+                "The world grid is {}x{}, but must be at least {}x{}",
+                width, height, TILE_SIZE, TILE_SIZE
+            ),
+            WorldError::MissingEntityDefinition { identifier } => {
+                write!(f, "Could not find the '{}' entity definition", identifier)
+            }
+            WorldError::MissingStartPoint => write!(
+                f,
+                "Could not find a '{}' entity in any level",
+                START_POINT_IDENTIFIER
+            ),
+            WorldError::MissingLayers { level } => {
+                write!(f, "Level '{}' has no layer data", level)
+            }
+            WorldError::MissingTileset {
+                level,
+                layer,
+                tileset_uid,
+            } => write!(
+                f,
+                "Layer '{}' of level '{}' uses tileset #{}, which has no image",
+                layer, level, tileset_uid
+            ),
+            WorldError::IntGridSizeMismatch {
+                level,
+                layer,
+                expected,
+                found,
+            } => write!(
+                f,
+                "IntGrid layer '{}' of level '{}' has {} cells, expected {}",
+                layer, level, found, expected
+            ),
+            WorldError::MissingCollisionLayer { level } => {
+                write!(f, "Level '{}' has no Collision layer", level)
+            }
+            WorldError::LayerSizeMismatch { level, layer } => write!(
+                f,
+                "IntGrid layer '{}' does not match the size of level '{}'",
+                layer, level
+            ),
+            WorldError::CollisionGridSizeMismatch {
+                level,
+                layer,
+                grid_size,
+            } => write!(
+                f,
+                "Layer '{}' of level '{}' has a grid size of {}, expected {}",
+                layer, level, grid_size, TILE_SIZE
+            ),
+            WorldError::TileGridSizeMismatch {
+                level,
+                layer,
+                layer_grid_size,
+                tileset_grid_size,
+            } => write!(
+                f,
+                "Layer '{}' of level '{}' has a grid size of {}, but its tileset uses {}",
+                layer, level, layer_grid_size, tileset_grid_size
+            ),
+            WorldError::UnreachableLevel { level } => {
+                write!(f, "Level '{}' can't be reached from the start point", level)
+            }
+            WorldError::InvalidDoorTarget {
+                level,
+                entity,
+                target,
+            } => write!(
+                f,
+                "'{}' entity in level '{}' targets entity {}, which is not a door",
+                entity, level, target
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+impl WorldProject {
+    pub fn validate(&self) -> Result<(), Vec<WorldError>> {
+        let mut errors = Vec::new();
+
+        match self.world_grid {
+            None => errors.push(WorldError::MissingWorldGrid),
+            Some((width, height)) if width < TILE_SIZE as i64 || height < TILE_SIZE as i64 => {
+                errors.push(WorldError::WorldGridTooSmall { width, height })
+            }
+            Some(_) => {}
+        }
+
+        if self
+            .entity_definitions
+            .as_ref()
+            .map_or(false, |defs| !defs.contains(START_POINT_IDENTIFIER))
+        {
+            errors.push(WorldError::MissingEntityDefinition {
+                identifier: START_POINT_IDENTIFIER.to_owned(),
+            });
+        } else if self.find_entity(START_POINT_IDENTIFIER).is_none() {
+            errors.push(WorldError::MissingStartPoint);
+        }
+
+        for level in self.levels.iter() {
+            let layers = if let Some(layers) = &level.layers {
+                layers
+            } else {
+                errors.push(WorldError::MissingLayers {
+                    level: level.identifier.clone(),
+                });
+                continue;
+            };
+
+            for layer in layers.iter() {
+                if let Some(tileset_uid) = layer.tileset_uid {
+                    let texture = self
+                        .tilesets
+                        .get(&tileset_uid)
+                        .and_then(|tileset| tileset.texture.as_ref());
+                    if texture.is_none() {
+                        errors.push(WorldError::MissingTileset {
+                            level: level.identifier.clone(),
+                            layer: layer.identifier.clone(),
+                            tileset_uid,
+                        });
+                    }
+                }
+
+                if let Some(int_grid) = &layer.int_grid {
+                    let expected = (layer.c_wid * layer.c_hei) as usize;
+                    if int_grid.len() != expected {
+                        errors.push(WorldError::IntGridSizeMismatch {
+                            level: level.identifier.clone(),
+                            layer: layer.identifier.clone(),
+                            expected,
+                            found: int_grid.len(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl WorldProject {
+    /// Looks for level design mistakes which don't prevent the world from loading, but most likely
+    /// are unintended. Meant to be used along with [`WorldProject::validate`] by tools.
+    pub fn lint(&self) -> Vec<WorldError> {
+        let mut errors = Vec::new();
+
+        for level in self.levels.iter() {
+            let layers = if let Some(layers) = &level.layers {
+                layers
+            } else {
+                continue;
+            };
+
+            if level.layer(COLLISION_LAYER).is_none() {
+                errors.push(WorldError::MissingCollisionLayer {
+                    level: level.identifier.clone(),
+                });
+            }
+
+            for layer in layers.iter() {
+                if layer.int_grid.is_some()
+                    && (layer.c_wid * layer.grid_size != level.px_wid
+                        || layer.c_hei * layer.grid_size != level.px_hei)
+                {
+                    errors.push(WorldError::LayerSizeMismatch {
+                        level: level.identifier.clone(),
+                        layer: layer.identifier.clone(),
+                    });
+                }
+
+                if COLLISION_LAYERS.contains(&layer.identifier.as_str())
+                    && layer.grid_size != TILE_SIZE as i64
+                {
+                    errors.push(WorldError::CollisionGridSizeMismatch {
+                        level: level.identifier.clone(),
+                        layer: layer.identifier.clone(),
+                        grid_size: layer.grid_size,
+                    });
+                }
+
+                if let Some(tileset) = layer.tileset_uid.and_then(|uid| self.tilesets.get(&uid)) {
+                    if tileset.tile_size != layer.grid_size {
+                        errors.push(WorldError::TileGridSizeMismatch {
+                            level: level.identifier.clone(),
+                            layer: layer.identifier.clone(),
+                            layer_grid_size: layer.grid_size,
+                            tileset_grid_size: tileset.tile_size,
+                        });
+                    }
+                }
+            }
+        }
+
+        errors.extend(self.unreachable_levels().into_iter().map(|level| {
+            WorldError::UnreachableLevel {
+                level: level.identifier.clone(),
+            }
+        }));
+
+        let doors: HashSet<&str> = self
+            .entities()
+            .filter(|(_, entity)| entity.identifier == DOOR_IDENTIFIER)
+            .map(|(_, entity)| entity.iid.as_str())
+            .collect();
+        for (level, entity) in self.entities() {
+            for target in entity.field("Targets").map(entity_refs).unwrap_or_default() {
+                if !doors.contains(target.as_str()) {
+                    errors.push(WorldError::InvalidDoorTarget {
+                        level: level.identifier.clone(),
+                        entity: entity.identifier.clone(),
+                        target,
+                    });
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Returns the levels that can't be walked into from the level containing the start point.
+    ///
+    /// Two levels are considered connected if they are next to each other and there is at least
+    /// one non-solid tile on both sides of the edge they share.
+    pub fn unreachable_levels(&self) -> Vec<&Level> {
+        let start_level = if let Some((level, _)) = self.find_entity(START_POINT_IDENTIFIER) {
+            level
+        } else {
+            return Vec::new();
+        };
+
+        let mut reached = HashSet::new();
+        let mut to_visit = VecDeque::new();
+        reached.insert(start_level.identifier.as_str());
+        to_visit.push_back(start_level);
+
+        while let Some(level) = to_visit.pop_front() {
+            for other in self.levels.iter() {
+                if !reached.contains(other.identifier.as_str()) && self.are_connected(level, other)
+                {
+                    reached.insert(other.identifier.as_str());
+                    to_visit.push_back(other);
+                }
+            }
+        }
+
+        self.levels
+            .iter()
+            .filter(|level| !reached.contains(level.identifier.as_str()))
+            .collect()
+    }
+
+    fn are_connected(&self, a: &Level, b: &Level) -> bool {
+        // get_tile takes bevy coordinates, which are +Y up
+        let is_open = |x: i64, y: i64| !self.get_tile(x, -y).contains(LevelTile::SOLID);
+        let (a, b) = (TileRect::of_level(a), TileRect::of_level(b));
+
+        [(a, b), (b, a)].into_iter().any(|(first, second)| {
+            let shares_vertical_edge = first.max_x == second.min_x
+                && (first.min_y.max(second.min_y)..first.max_y.min(second.max_y))
+                    .any(|y| is_open(first.max_x - 1, y) && is_open(second.min_x, y));
+            let shares_horizontal_edge = first.max_y == second.min_y
+                && (first.min_x.max(second.min_x)..first.max_x.min(second.max_x))
+                    .any(|x| is_open(x, first.max_y - 1) && is_open(x, second.min_y));
+
+            shares_vertical_edge || shares_horizontal_edge
+        })
+    }
+}
+
+/// Errors found in the last world that was loaded.
+#[derive(Default, Debug)]
+pub struct WorldErrors(pub Vec<WorldError>);
+
+/// Validates the world whenever it is (re)loaded or switched to. Moves to [`AppState::Error`] if it
+/// is malformed, or back to [`AppState::Playing`] once the errors have been fixed. Switching to a
+/// world that is still loading moves to [`AppState::Loading`] until it is ready.
+///
+/// Transitions overwrite any other one queued during the same frame, such as pausing, since the
+/// game can't be played until the world is valid.
+pub fn validate_loaded_world(
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    asset_server: Res<AssetServer>,
+    maps: Res<Assets<WorldProject>>,
+    world: Res<GameWorld>,
+    mut errors: ResMut<WorldErrors>,
+    mut state: ResMut<State<AppState>>,
+) {
+    // Files that fail to parse never send an asset event
+    if asset_server.get_load_state(&world.project) == LoadState::Failed {
+        let path = asset_server
+            .get_handle_path(&world.project)
+            .map_or_else(String::new, |path| path.path().display().to_string());
+        let new_errors = vec![WorldError::LoadFailed { path }];
+        if errors.0 != new_errors {
+            error!("{}", new_errors[0]);
+            errors.0 = new_errors;
+        }
+        if state.current() != &AppState::Error {
+            state.overwrite_set(AppState::Error).unwrap();
+        }
+        return;
+    }
+
+    if !world_changed(&world, &mut map_events) {
+        return;
+    }
+
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        // Switched to a world which hasn't been loaded yet
+        if state.current() == &AppState::Playing {
+            state.overwrite_set(AppState::Loading).unwrap();
+        }
+        return;
+    };
+
+    match map.validate() {
+        Ok(()) => {
+            errors.0.clear();
+            if matches!(state.current(), AppState::Loading | AppState::Error) {
+                info!("Finished loading level");
+                state.overwrite_set(AppState::Playing).unwrap();
+            }
+        }
+        Err(new_errors) => {
+            for error in new_errors.iter() {
+                error!("{}", error);
+            }
+            errors.0 = new_errors;
+            if state.current() != &AppState::Error {
+                state.overwrite_set(AppState::Error).unwrap();
+            }
+        }
+    }
+}
+
+pub fn show_world_errors(errors: Res<WorldErrors>, mut egui: ResMut<bevy_egui::EguiContext>) {
+    egui::CentralPanel::default().show(egui.ctx_mut(), |ui| {
+        ui.heading("The world could not be loaded");
+        ui.label("Fix the following errors and save the project to reload it:");
+        ui.separator();
+        for error in errors.0.iter() {
+            ui.label(format!("• {}", error));
+        }
+    });
+}
@@ -0,0 +1,213 @@
+//! Warps that move the player to another place of the world, spawned from `Warp` LDtk entities.
+//!
+//! Warps are configured through the following entity fields:
+//! - `Destination` (Entity reference): Entity to move the player to. It may be in another level.
+//! - `World` and `Entry_Point` (String): Path of another world file, relative to the assets folder,
+//!   and name of the `Entry_Point` entity to place the player at. Used instead of `Destination`.
+//! - `Interact` (Bool): Whether the warp is used through the interact action instead of by touching
+//!   it.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    doors::entity_refs,
+    input_mapper::{self, Input},
+    level::{EntityInstance, Level},
+    physics::{RectCollision, RectExtras},
+    transition::{PlayTransition, RoomTransitionSettings},
+    world::{entity_rect, world_changed, GameWorld},
+    worlds::SwitchWorld,
+    AppState, Player, WorldProject,
+};
+
+pub const WARP_IDENTIFIER: &str = "Warp";
+
+const WARP_TRANSITION_DURATION: Duration = Duration::from_millis(600);
+const WARP_Z: f32 = 9.;
+const WARP_COLOR: Color = Color::rgba(0.6, 0.3, 0.9, 0.6);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WarpDestination {
+    /// IID of an entity of the current world.
+    Entity(String),
+    World {
+        path: String,
+        entry_point: String,
+    },
+}
+
+#[derive(Component, Debug)]
+pub struct Warp {
+    pub destination: WarpDestination,
+    pub interact: bool,
+}
+
+/// Warps only trigger once the player has stepped off every warp, so that arriving at a warp
+/// doesn't immediately send the player back.
+struct WarpsArmed(bool);
+
+impl Default for WarpsArmed {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+pub struct WarpPlugin;
+
+impl Plugin for WarpPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WarpsArmed>()
+            .add_system(spawn_warps)
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(use_warps));
+    }
+}
+
+/// Returns where the player should be placed to stand on the given entity, in bevy units.
+pub fn arrival_position(level: &Level, entity: &EntityInstance) -> Vec2 {
+    let rect = entity_rect(level, entity);
+    // The player is one tile tall and centered on its position
+    Vec2::new((rect.min.x + rect.max.x) / 2., rect.min.y + 0.5)
+}
+
+fn spawn_warps(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    maps: Res<Assets<WorldProject>>,
+    world: Res<GameWorld>,
+    spawned: Query<Entity, With<Warp>>,
+) {
+    if !world_changed(&world, &mut map_events) {
+        return;
+    }
+
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (level, entity) in map
+        .entities()
+        .filter(|(_, entity)| entity.identifier == WARP_IDENTIFIER)
+    {
+        let string_field = |identifier| {
+            entity
+                .field(identifier)
+                .and_then(|value| value.as_str())
+                .map(str::to_owned)
+        };
+
+        let destination = match (
+            string_field("World"),
+            string_field("Entry_Point"),
+            entity
+                .field("Destination")
+                .and_then(|value| entity_refs(value).pop()),
+        ) {
+            (Some(path), Some(entry_point), _) => WarpDestination::World { path, entry_point },
+            (None, None, Some(iid)) => WarpDestination::Entity(iid),
+            _ => {
+                warn!(
+                    "Warp in level '{}' needs either a Destination or a World and an Entry_Point",
+                    level.identifier
+                );
+                continue;
+            }
+        };
+
+        let rect = entity_rect(level, entity);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: WARP_COLOR,
+                    custom_size: Some(rect.size()),
+                    ..default()
+                },
+                transform: Transform::from_translation(((rect.min + rect.max) / 2.).extend(WARP_Z)),
+                ..default()
+            })
+            .insert(RectCollision::centered(rect.size()))
+            .insert(Warp {
+                destination,
+                interact: entity
+                    .field("Interact")
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or(false),
+            });
+    }
+}
+
+fn use_warps(
+    input: Res<Input>,
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    mut armed: ResMut<WarpsArmed>,
+    player: Query<(&GlobalTransform, &RectCollision), With<Player>>,
+    warps: Query<(&GlobalTransform, &RectCollision, &Warp)>,
+    mut transition_events: EventWriter<PlayTransition>,
+) {
+    let (map, player_rect) = match (maps.get(&world.project), player.get_single()) {
+        (Some(map), Ok((transform, collision))) => (
+            map,
+            collision.rect.translate(transform.translation.truncate()),
+        ),
+        _ => return,
+    };
+    let interacting =
+        input.actions[input_mapper::Action::Interact] == input_mapper::ActionState::JustPressed;
+
+    let mut touching_any = false;
+    for (transform, collision, warp) in warps.iter() {
+        if !collision
+            .rect
+            .translate(transform.translation.truncate())
+            .intersects(player_rect)
+        {
+            continue;
+        }
+        touching_any = true;
+
+        if !armed.0 || (warp.interact && !interacting) {
+            continue;
+        }
+
+        let (teleport_to, switch_world) = match &warp.destination {
+            WarpDestination::Entity(iid) => match map.entity_by_iid(iid) {
+                Some((level, entity)) => (Some(arrival_position(level, entity)), None),
+                None => {
+                    warn!("Warp destination {} does not exist", iid);
+                    continue;
+                }
+            },
+            WarpDestination::World { path, entry_point } => (
+                None,
+                Some(SwitchWorld {
+                    path: path.clone(),
+                    entry_point: Some(entry_point.clone()),
+                }),
+            ),
+        };
+
+        transition_events.send(PlayTransition {
+            settings: RoomTransitionSettings {
+                duration: WARP_TRANSITION_DURATION,
+                freeze: WARP_TRANSITION_DURATION,
+                fade: true,
+            },
+            teleport_to,
+            switch_world,
+        });
+        armed.0 = false;
+        break;
+    }
+
+    if !touching_any && !armed.0 {
+        armed.0 = true;
+    }
+}
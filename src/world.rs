@@ -1,78 +1,70 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+use crate::follow::CameraFollow;
+use crate::ldtk::{self, LdtkSource};
+use crate::level::{EntityInstance, Level, Tileset, COLLISION_LAYER, HAZARD_LAYER, PLATFORM_LAYER};
 use crate::physics::RectExtras;
 use crate::physics::StaticBody;
-use crate::AppState;
+use crate::tile_animation::{animate_tiles, TileAnimation};
+use crate::tiled::{self, TiledSource};
 
-use bevy::asset::{AssetPath, LoadedAsset};
+use bevy::asset::HandleId;
 
 use bevy::math::{vec2, vec3};
+use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
 use bevy::render::render_resource::TextureUsages;
 use bevy::sprite::Rect;
-use bevy::{asset::AssetLoader, prelude::*};
 use bevy_ecs_tilemap::{
     Chunk, ChunkPos, ChunkSize, Layer, LayerBuilder, LayerBundle, LayerSettings, Map, MapSize,
     TextureSize, TileBundle, TilePos, TileSize, TilemapPlugin,
 };
 
+/// A world loaded from any of the supported level editor formats.
 #[derive(TypeUuid)]
 #[uuid = "ace787fd-c5d2-4651-a42b-f08fa985676c"]
-pub struct LdtkProject {
-    pub project: ldtk_rust::Project,
-    pub tilesets: HashMap<i64, Handle<Image>>,
+pub struct WorldProject {
+    pub levels: Vec<Level>,
+    /// Tilesets, indexed by UID.
+    pub tilesets: HashMap<i64, Tileset>,
+    /// Animations of tiles, indexed by tileset UID and tile ID.
+    pub tile_animations: HashMap<(i64, u16), TileAnimation>,
+    /// Size of the cells of the world grid in pixels, which room tracking is based on.
+    pub world_grid: Option<(i64, i64)>,
+    /// Identifiers of the entities defined in the world, if its format has entity definitions.
+    pub entity_definitions: Option<HashSet<String>>,
+    pub source: WorldSource,
 }
 
-pub struct LdtkLoader;
-
-impl AssetLoader for LdtkLoader {
-    fn load<'a>(
-        &'a self,
-        bytes: &'a [u8],
-        load_context: &'a mut bevy::asset::LoadContext,
-    ) -> bevy::asset::BoxedFuture<'a, anyhow::Result<(), anyhow::Error>> {
-        Box::pin(async move {
-            let project: ldtk_rust::Project = serde_json::from_slice(bytes)?;
-            let dependencies: Vec<(i64, AssetPath)> = project
-                .defs
-                .tilesets
-                .iter()
-                .filter_map(|tileset| {
-                    tileset.rel_path.as_ref().map(|path| {
-                        (
-                            tileset.uid,
-                            load_context
-                                .path()
-                                .parent()
-                                .unwrap()
-                                .join(path.clone())
-                                .into(),
-                        )
-                    })
-                })
-                .collect();
-            let loaded_asset = LoadedAsset::new(LdtkProject {
-                project,
-                tilesets: dependencies
-                    .iter()
-                    .map(|dep| (dep.0, load_context.get_handle(dep.1.clone())))
-                    .collect(),
-            });
-            load_context.set_default_asset(
-                loaded_asset.with_dependencies(dependencies.iter().map(|x| x.1.clone()).collect()),
-            );
-
-            Ok(())
-        })
-    }
-
-    fn extensions(&self) -> &[&str] {
-        &["ldtk"]
-    }
+/// Format-specific data of a world.
+pub enum WorldSource {
+    Ldtk(LdtkSource),
+    Tiled(TiledSource),
+    /// Laid out by [`crate::generator`] from the rooms of another world.
+    Generated,
 }
 
 pub struct GameWorld {
-    pub ldtk: Handle<LdtkProject>,
+    pub project: Handle<WorldProject>,
+}
+
+/// Returns whether the active world was switched to, loaded or modified since the calling system
+/// last ran. Systems that spawn entities from the world should use this instead of asset events
+/// alone, since switching back to an already loaded world doesn't send any.
+pub fn world_changed(
+    world: &Res<GameWorld>,
+    map_events: &mut EventReader<AssetEvent<WorldProject>>,
+) -> bool {
+    let project_changed = map_events
+        .iter()
+        .filter(|event| {
+            matches!(event, AssetEvent::Modified { handle } | AssetEvent::Created { handle } if handle == &world.project)
+        })
+        .count()
+        > 0;
+
+    project_changed || world.is_changed()
 }
 
 bitflags::bitflags! {
@@ -80,19 +72,125 @@ bitflags::bitflags! {
     pub struct LevelTile: u8 {
         const SOLID = 0b0001;
         const PLATFORM = 0b0010;
+        const HAZARD = 0b0100;
     }
 }
 
 pub const TILE_SIZE: u32 = 16;
 
-impl LdtkProject {
+/// Area of a level in tiles, using LDtk coordinates (+Y down). The maximum is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileRect {
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+}
+
+impl TileRect {
+    pub fn of_level(level: &Level) -> Self {
+        let tile_size = TILE_SIZE as i64;
+        // Rounds towards negative infinity so that levels left of or above the origin line up
+        Self {
+            min_x: level.world_x.div_euclid(tile_size),
+            min_y: level.world_y.div_euclid(tile_size),
+            max_x: (level.world_x + level.px_wid).div_euclid(tile_size),
+            max_y: (level.world_y + level.px_hei).div_euclid(tile_size),
+        }
+    }
+
+    pub fn width(&self) -> i64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> i64 {
+        self.max_y - self.min_y
+    }
+
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        self.min_x <= x && x < self.max_x && self.min_y <= y && y < self.max_y
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+
+    /// Returns the smallest rect containing both rects.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+/// Returns the area covered by an entity instance, in bevy units.
+pub fn entity_rect(level: &Level, entity: &EntityInstance) -> Rect {
+    let size = vec2(entity.width as f32, entity.height as f32);
+    // Top left corner in world pixels
+    let min = vec2(
+        (level.world_x + entity.px[0]) as f32,
+        (level.world_y + entity.px[1]) as f32,
+    ) - entity.pivot * size;
+
+    // Tile centers are at integer bevy coordinates, and world pixel coordinates are +Y down
+    let size = size / TILE_SIZE as f32;
+    let min = vec2(
+        min.x / TILE_SIZE as f32 - 0.5,
+        -min.y / TILE_SIZE as f32 + 0.5 - size.y,
+    );
+    Rect::from_min_size(min, size)
+}
+
+impl WorldProject {
+    /// Reads a world straight from disk, without an asset server. The format is chosen from the
+    /// file extension.
+    ///
+    /// Meant for command line tools: tilesets get a placeholder texture if their image exists, so
+    /// the world can still be validated.
+    pub fn load_from_file(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ldtk") => ldtk::load_from_file(path),
+            Some("tmx" | "tmj" | "world") => tiled::load_from_file(path),
+            _ => anyhow::bail!("Unsupported world format: {}", path.display()),
+        }
+    }
+
+    pub fn level(&self, identifier: &str) -> Option<&Level> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+    }
+
+    /// Returns the first instance of the entity with the given identifier and the level it is in.
+    pub fn find_entity(&self, identifier: &str) -> Option<(&Level, &EntityInstance)> {
+        self.entities()
+            .find(|(_, entity)| entity.identifier == identifier)
+    }
+
+    /// Returns every entity instance in the world along with the level it is in.
+    pub fn entities(&self) -> impl Iterator<Item = (&Level, &EntityInstance)> {
+        self.levels
+            .iter()
+            .flat_map(|level| level.entities.iter().map(move |entity| (level, entity)))
+    }
+
+    /// Returns the entity instance with the given IID and the level it is in.
+    pub fn entity_by_iid(&self, iid: &str) -> Option<(&Level, &EntityInstance)> {
+        self.entities().find(|(_, entity)| entity.iid == iid)
+    }
+
     /// Coordinates given are in bevy units
     pub fn get_tile(&self, x: i64, y: i64) -> LevelTile {
-        // LDTK coordinates are +Y Down, Bevy coordinates are +Y Up
+        // World pixel coordinates are +Y Down, Bevy coordinates are +Y Up
         let y = -y;
 
-        self.project
-            .levels
+        self.levels
             .iter()
             .find(|level| {
                 Rect::from_min_size(
@@ -109,74 +207,79 @@ impl LdtkProject {
             })
             .map_or(LevelTile::empty(), |level| {
                 let (local_x, local_y) = (
-                    x - level.world_x / TILE_SIZE as i64,
-                    y - level.world_y / TILE_SIZE as i64,
+                    x - level.world_x.div_euclid(TILE_SIZE as i64),
+                    y - level.world_y.div_euclid(TILE_SIZE as i64),
                 );
-
-                let collision = level
-                    .layer_instances
-                    .iter()
-                    .flatten()
-                    .find(|layer| layer.identifier == "Collision")
-                    .map_or(LevelTile::empty(), |layer| {
+                let is_set = |identifier| {
+                    level.layer(identifier).map_or(false, |layer| {
                         let idx = local_x + local_y * layer.c_wid;
-                        if layer.int_grid_csv[idx as usize] != 0 {
-                            LevelTile::SOLID
-                        } else {
-                            LevelTile::empty()
-                        }
-                    });
+                        layer
+                            .int_grid
+                            .as_ref()
+                            .and_then(|int_grid| int_grid.get(idx as usize))
+                            .map_or(false, |&value| value != 0)
+                    })
+                };
 
-                if !collision.is_empty() {
-                    collision
+                if is_set(COLLISION_LAYER) {
+                    LevelTile::SOLID
+                } else if is_set(PLATFORM_LAYER) {
+                    LevelTile::PLATFORM
+                } else if is_set(HAZARD_LAYER) {
+                    LevelTile::HAZARD
                 } else {
-                    level
-                        .layer_instances
-                        .iter()
-                        .flatten()
-                        .find(|layer| layer.identifier == "Platforms")
-                        .map_or(LevelTile::empty(), |layer| {
-                            let idx = local_x + local_y * layer.c_wid;
-                            if layer.int_grid_csv[idx as usize] != 0 {
-                                LevelTile::PLATFORM
-                            } else {
-                                LevelTile::empty()
-                            }
-                        })
+                    LevelTile::empty()
                 }
             })
     }
 }
 
-pub fn change_to_playing_state_on_level_load(
-    mut map_events: EventReader<AssetEvent<LdtkProject>>,
-    mut state: ResMut<State<AppState>>,
-) {
-    if map_events
-        .iter()
-        .any(|event| matches!(event, AssetEvent::Created { .. }))
-    {
-        info!("Finished loading level");
-        state.set(AppState::Playing).unwrap();
-    }
-}
-
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(TilemapPlugin)
-            .add_asset::<LdtkProject>()
-            .add_asset_loader(LdtkLoader)
+            .add_asset::<WorldProject>()
+            .add_asset_loader(ldtk::LdtkLoader)
+            .add_asset::<ldtk::LdtkLevel>()
+            .add_asset_loader(ldtk::LdtkLevelLoader)
+            .add_asset_loader(tiled::TiledMapLoader)
+            .add_asset_loader(tiled::TiledWorldLoader)
+            .add_asset::<tiled::TiledTilesetFile>()
+            .add_asset_loader(tiled::TiledTilesetLoader)
+            .add_system_to_stage(CoreStage::PreUpdate, ldtk::reload_external_levels)
+            .add_system_to_stage(CoreStage::PreUpdate, tiled::reload_tiled_worlds)
             .add_system_to_stage(CoreStage::PreUpdate, spawn_maps)
             .add_system(process_loaded_tile_maps.after(spawn_maps))
-            .add_system(set_texture_usages.before(process_loaded_tile_maps));
+            .add_system(set_texture_usages.before(process_loaded_tile_maps))
+            .add_system(animate_tiles.after(process_loaded_tile_maps))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_layer_parallax.after(crate::follow::follow),
+            );
     }
 }
 
 #[derive(Component, Deref, DerefMut, Default, Clone)]
 pub struct LevelId(pub String);
 
+/// Parallax scrolling of a tile layer.
+///
+/// A factor of 0 scrolls along with the level, positive factors make the layer look further away
+/// and negative ones closer, like the LDtk editor does.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct LayerParallax {
+    pub factor: Vec2,
+    /// Translation of the layer relative to its level when no parallax is applied.
+    pub origin: Vec3,
+    /// Center of the level the layer belongs to, in world coordinates.
+    pub level_center: Vec2,
+}
+
+/// Marks maps whose layers haven't been built yet by [`process_loaded_tile_maps`].
+#[derive(Component)]
+pub struct UnbuiltMap;
+
 #[derive(Bundle, Default)]
 pub struct LevelBundle {
     pub map: Map,
@@ -202,144 +305,164 @@ pub fn set_texture_usages(
     }
 }
 
+/// Despawns all the layers of a tile map, along with their chunks and tiles.
+fn despawn_map_layers(
+    commands: &mut Commands,
+    map: &mut Map,
+    layer_query: &Query<&Layer>,
+    chunk_query: &Query<&Chunk>,
+) {
+    for (layer_id, layer_entity) in map.get_layers() {
+        if let Ok(layer) = layer_query.get(layer_entity) {
+            for x in 0..layer.get_layer_size_in_tiles().0 {
+                for y in 0..layer.get_layer_size_in_tiles().1 {
+                    let tile_pos = TilePos(x, y);
+                    let chunk_pos = ChunkPos(
+                        tile_pos.0 / layer.settings.chunk_size.0,
+                        tile_pos.1 / layer.settings.chunk_size.1,
+                    );
+                    if let Some(chunk_entity) = layer.get_chunk(chunk_pos) {
+                        if let Ok(chunk) = chunk_query.get(chunk_entity) {
+                            let chunk_tile_pos = chunk.to_chunk_pos(tile_pos);
+                            if let Ok(chunk_tile_pos) = chunk_tile_pos {
+                                if let Some(tile) = chunk.get_tile_entity(chunk_tile_pos) {
+                                    commands.entity(tile).despawn_recursive();
+                                }
+                            }
+                        }
+
+                        commands.entity(chunk_entity).despawn_recursive();
+                    }
+                }
+            }
+        }
+        map.remove_layer(commands, layer_id);
+    }
+}
+
+/// Spawns a map for each level of the active world once it has loaded, despawning the ones of the
+/// previous world if there was one.
+///
 /// Must be called one stage before [`process_loaded_tile_maps`] in order for the entities to be spawned and loaded correctly
 pub fn spawn_maps(
     mut commands: Commands,
-    mut map_events: EventReader<AssetEvent<LdtkProject>>,
-    maps: Res<Assets<LdtkProject>>,
+    maps: Res<Assets<WorldProject>>,
     world: Res<GameWorld>,
+    mut spawned_world: Local<Option<HandleId>>,
+    mut level_maps: Query<(Entity, &mut Map), With<LevelId>>,
+    layer_query: Query<&Layer>,
+    chunk_query: Query<&Chunk>,
 ) {
-    let added_map = map_events
-        .iter()
-        .any(|event| matches!(event, AssetEvent::Created{handle} if handle == &world.ldtk));
-
-    if !added_map {
+    if *spawned_world == Some(world.project.id) {
         return;
     }
 
-    let map = maps.get(&world.ldtk).unwrap();
+    let map = if let Some(map) = maps.get(&world.project) {
+        map
+    } else {
+        return;
+    };
+
+    for (entity, mut level_map) in level_maps.iter_mut() {
+        despawn_map_layers(&mut commands, &mut level_map, &layer_query, &chunk_query);
+        commands.entity(entity).despawn_recursive();
+    }
 
-    for level in map.project.levels.iter() {
+    for level in map.levels.iter() {
         info!("Spawning level '{}'", &level.identifier);
         let map_entity = commands.spawn().id();
 
-        commands.entity(map_entity).insert_bundle(LevelBundle {
-            map: Map::new(level.uid as u16, map_entity),
-            transform: Transform::from_xyz(
-                level.world_x as f32 / 16.0,
-                -level.world_y as f32 / 16.0,
-                0.0,
-            ),
-            level_id: LevelId(level.identifier.clone()),
-            ..Default::default()
-        });
+        commands
+            .entity(map_entity)
+            .insert_bundle(LevelBundle {
+                map: Map::new(level.uid as u16, map_entity),
+                transform: Transform::from_xyz(
+                    level.world_x as f32 / 16.0,
+                    -level.world_y as f32 / 16.0,
+                    0.0,
+                ),
+                level_id: LevelId(level.identifier.clone()),
+                ..Default::default()
+            })
+            .insert(UnbuiltMap);
     }
+
+    *spawned_world = Some(world.project.id);
 }
 
+/// Builds the tile layers of newly spawned maps, and rebuilds all of them when the project is
+/// modified.
 pub fn process_loaded_tile_maps(
     mut commands: Commands,
-    mut map_events: EventReader<AssetEvent<LdtkProject>>,
-    maps: Res<Assets<LdtkProject>>,
+    mut map_events: EventReader<AssetEvent<WorldProject>>,
+    maps: Res<Assets<WorldProject>>,
     world: Res<GameWorld>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut query: Query<(Entity, &LevelId, &mut Map)>,
+    mut query: Query<(Entity, &LevelId, &mut Map, Option<&UnbuiltMap>)>,
     layer_query: Query<&Layer>,
     chunk_query: Query<&Chunk>,
 ) {
-    let changed_project = map_events
+    let modified_project = map_events
         .iter()
         .inspect(|x| info!("{:?}", x))
-        .any(|event| matches!(event, AssetEvent::Modified { handle } | AssetEvent::Created { handle } if handle == &world.ldtk));
-
-    if !changed_project {
-        return;
+        .filter(
+            |event| matches!(event, AssetEvent::Modified { handle } if handle == &world.project),
+        )
+        .count()
+        > 0;
+
+    if modified_project {
+        info!("Project was changed, updating map");
     }
 
-    info!("Project was changed, updating map");
+    for (entity, level_id, mut map, unbuilt) in query.iter_mut() {
+        if unbuilt.is_some() {
+            commands.entity(entity).remove::<UnbuiltMap>();
+        } else if !modified_project {
+            continue;
+        }
 
-    for (_, level_id, mut map) in query.iter_mut() {
-        if let Some(ldtk_map) = maps.get(&world.ldtk) {
+        if let Some(project) = maps.get(&world.project) {
             // Despawn all tiles/chunks/layers.
-            for (layer_id, layer_entity) in map.get_layers() {
-                if let Ok(layer) = layer_query.get(layer_entity) {
-                    for x in 0..layer.get_layer_size_in_tiles().0 {
-                        for y in 0..layer.get_layer_size_in_tiles().1 {
-                            let tile_pos = TilePos(x, y);
-                            let chunk_pos = ChunkPos(
-                                tile_pos.0 / layer.settings.chunk_size.0,
-                                tile_pos.1 / layer.settings.chunk_size.1,
-                            );
-                            if let Some(chunk_entity) = layer.get_chunk(chunk_pos) {
-                                if let Ok(chunk) = chunk_query.get(chunk_entity) {
-                                    let chunk_tile_pos = chunk.to_chunk_pos(tile_pos);
-                                    if let Ok(chunk_tile_pos) = chunk_tile_pos {
-                                        if let Some(tile) = chunk.get_tile_entity(chunk_tile_pos) {
-                                            commands.entity(tile).despawn_recursive();
-                                        }
-                                    }
-                                }
-
-                                commands.entity(chunk_entity).despawn_recursive();
-                            }
-                        }
-                    }
-                }
-                map.remove_layer(&mut commands, layer_id);
-            }
-
-            // Pull out tilesets.
-            let mut tilesets = HashMap::new();
-            ldtk_map
-                .project
-                .defs
-                .tilesets
-                .iter()
-                // Filter out internal icons
-                .filter(|tileset| tileset.rel_path.is_some())
-                .for_each(|tileset| {
-                    tilesets.insert(
-                        tileset.uid,
-                        (
-                            ldtk_map.tilesets.get(&tileset.uid).unwrap().clone(),
-                            tileset,
-                        ),
-                    );
-                });
-
-            let default_grid_size = ldtk_map.project.default_grid_size;
-            let level = ldtk_map
-                .project
-                .levels
-                .iter()
-                .find(|&l| l.identifier == **level_id)
-                .unwrap();
-
-            let level_tile_count_x = (level.px_wid / default_grid_size) as u32;
-            let level_tile_count_y = (level.px_hei / default_grid_size) as u32;
-
-            let map_size = MapSize(
-                (level_tile_count_x as f32 / 32.0).ceil() as u32,
-                (level_tile_count_y as f32 / 32.0).ceil() as u32,
-            );
-
-            for (layer_id, layer) in level
-                .layer_instances
-                .as_ref()
-                .unwrap()
-                .iter()
-                .rev()
-                .enumerate()
-            {
-                let (texture, tileset) = if let Some(uid) = layer.tileset_def_uid {
-                    tilesets.get(&uid).unwrap().clone()
+            despawn_map_layers(&mut commands, &mut map, &layer_query, &chunk_query);
+
+            let level = if let Some(level) = project.level(level_id) {
+                level
+            } else {
+                warn!("Level '{}' no longer exists in the project", **level_id);
+                continue;
+            };
+            let layers = if let Some(layers) = &level.layers {
+                layers
+            } else {
+                continue;
+            };
+
+            for (layer_id, layer) in layers.iter().rev().enumerate() {
+                // Layers without a tileset image (e.g. ones using internal icons) aren't drawn
+                let (tileset_uid, tileset, texture) = if let Some((uid, tileset, texture)) =
+                    layer.tileset_uid.and_then(|uid| {
+                        project.tilesets.get(&uid).and_then(|tileset| {
+                            tileset
+                                .texture
+                                .clone()
+                                .map(|texture| (uid, tileset, texture))
+                        })
+                    }) {
+                    (uid, tileset, texture)
                 } else {
                     continue;
                 };
 
+                let map_size = MapSize(
+                    (layer.c_wid as f32 / 32.0).ceil() as u32,
+                    (layer.c_hei as f32 / 32.0).ceil() as u32,
+                );
+
                 let settings = LayerSettings::new(
                     map_size,
                     ChunkSize(32, 32),
-                    TileSize(tileset.tile_grid_size as f32, tileset.tile_grid_size as f32),
+                    TileSize(tileset.tile_size as f32, tileset.tile_size as f32),
                     TextureSize(tileset.px_wid as f32, tileset.px_hei as f32),
                 );
 
@@ -350,48 +473,83 @@ pub fn process_loaded_tile_maps(
                     layer_id as u16,
                 );
 
-                let tileset_width_in_tiles = (tileset.px_wid / default_grid_size) as u32;
-
-                for tile in layer.auto_layer_tiles.iter().chain(layer.grid_tiles.iter()) {
-                    let tileset_x = (tile.src[0] / default_grid_size) as u32;
-                    let tileset_y = (tile.src[1] / default_grid_size) as u32;
+                let layer_color = Color::rgba(1., 1., 1., layer.opacity);
 
+                for tile in layer.tiles.iter() {
                     let mut pos = TilePos(
-                        (tile.px[0] / default_grid_size) as u32,
-                        (tile.px[1] / default_grid_size) as u32,
+                        (tile.px[0] / layer.grid_size) as u32,
+                        (tile.px[1] / layer.grid_size) as u32,
                     );
 
-                    pos.1 = level_tile_count_y - pos.1 - 1;
+                    pos.1 = layer.c_hei as u32 - pos.1 - 1;
 
                     layer_builder
                         .set_tile(
                             pos,
                             bevy_ecs_tilemap::Tile {
-                                texture_index: (tileset_y * tileset_width_in_tiles + tileset_x)
-                                    as u16,
+                                texture_index: tile.id,
+                                flip_x: tile.flip_x,
+                                flip_y: tile.flip_y,
+                                flip_d: tile.flip_d,
+                                color: layer_color,
                                 ..default()
                             }
                             .into(),
                         )
                         .unwrap();
+
+                    // Setting a tile clears its entity, so the animation goes on afterwards
+                    if let Some(animation) = project.tile_animations.get(&(tileset_uid, tile.id)) {
+                        let tile_entity =
+                            layer_builder.get_tile_entity(&mut commands, pos).unwrap();
+                        commands.entity(tile_entity).insert(animation.clone());
+                    }
                 }
 
-                let layer_bundle = layer_builder.build(&mut commands, &mut meshes, texture);
-                let layer = layer_bundle.layer;
                 let transform = Transform::from_xyz(
-                    0.,
-                    -level.px_hei as f32 / TILE_SIZE as f32,
+                    layer.px_offset_x as f32 / TILE_SIZE as f32,
+                    -(layer.c_hei * layer.grid_size + layer.px_offset_y) as f32 / TILE_SIZE as f32,
                     layer_id as f32,
                 )
                 .with_scale(vec3(1. / 16., 1. / 16., 1.));
 
+                let layer_bundle = layer_builder.build(&mut commands, &mut meshes, texture);
+
                 map.add_layer(&mut commands, layer_id as u16, layer_entity);
-                commands.entity(layer_entity).insert_bundle(LayerBundle {
-                    layer,
-                    transform,
-                    ..layer_bundle
-                });
+                commands
+                    .entity(layer_entity)
+                    .insert_bundle(LayerBundle {
+                        layer: layer_bundle.layer,
+                        transform,
+                        ..layer_bundle
+                    })
+                    .insert(LayerParallax {
+                        factor: layer.parallax_factor,
+                        origin: transform.translation,
+                        level_center: vec2(
+                            (level.world_x as f32 + level.px_wid as f32 / 2.) / TILE_SIZE as f32,
+                            -(level.world_y as f32 + level.px_hei as f32 / 2.) / TILE_SIZE as f32,
+                        ),
+                    });
             }
         }
     }
 }
+
+/// Offsets tile layers with a parallax factor depending on how far the camera is from the center of
+/// their level.
+pub fn update_layer_parallax(
+    camera: Query<&Transform, (With<Camera>, With<CameraFollow>)>,
+    mut layers: Query<(&LayerParallax, &mut Transform), Without<Camera>>,
+) {
+    let camera = if let Ok(camera) = camera.get_single() {
+        camera
+    } else {
+        return;
+    };
+
+    for (parallax, mut transform) in layers.iter_mut() {
+        let offset = (camera.translation.truncate() - parallax.level_center) * parallax.factor;
+        transform.translation = parallax.origin + offset.extend(0.);
+    }
+}
@@ -0,0 +1,142 @@
+//! Support for several LDtk worlds, e.g. an overworld and its dungeons, and switching between them.
+//!
+//! The active world is the one in [`GameWorld`]. Send a [`SwitchWorld`] event to change it: its
+//! tilemaps and entities are respawned once it has loaded, and the player is placed at the
+//! `Entry_Point` entity whose `Name` field matches the one requested, or at the `Start_Point` if
+//! none was.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::{
+    level::{EntityInstance, Level},
+    player::TeleportPlayer,
+    validation::START_POINT_IDENTIFIER,
+    warp::arrival_position,
+    world::GameWorld,
+    AppState, WorldProject,
+};
+
+pub const ENTRY_POINT_IDENTIFIER: &str = "Entry_Point";
+
+/// Worlds that have been loaded, by path relative to the assets folder. Their handles are kept so
+/// that switching back to a world doesn't load it again.
+#[derive(Default)]
+pub struct WorldRegistry {
+    pub worlds: HashMap<String, Handle<WorldProject>>,
+    /// Path of the active world.
+    pub current: Option<String>,
+}
+
+impl WorldRegistry {
+    /// Returns the handle of the world at the given path, loading it if it wasn't already.
+    pub fn load(&mut self, asset_server: &AssetServer, path: &str) -> Handle<WorldProject> {
+        self.worlds
+            .entry(path.to_owned())
+            .or_insert_with(|| asset_server.load(path))
+            .clone()
+    }
+}
+
+/// Makes the world at the given path the active one.
+#[derive(Debug, Clone)]
+pub struct SwitchWorld {
+    /// Path of the world, relative to the assets folder.
+    pub path: String,
+    /// Name of the entry point to place the player at once the world is ready. The start point is
+    /// used if `None`.
+    pub entry_point: Option<String>,
+}
+
+/// Switch whose player placement waits for the active world to be ready.
+#[derive(Default)]
+struct PendingSwitch(Option<SwitchWorld>);
+
+pub struct WorldsPlugin;
+
+impl Plugin for WorldsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldRegistry>()
+            .init_resource::<PendingSwitch>()
+            .add_event::<SwitchWorld>()
+            .add_system(switch_world)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing).with_system(place_player_at_entry_point),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading).with_system(show_loading_screen),
+            );
+    }
+}
+
+/// Returns the entry point with the given name and the level it is in.
+pub fn find_entry_point<'a>(
+    project: &'a WorldProject,
+    name: &str,
+) -> Option<(&'a Level, &'a EntityInstance)> {
+    project.entities().find(|(_, entity)| {
+        entity.identifier == ENTRY_POINT_IDENTIFIER
+            && entity.field("Name").and_then(|value| value.as_str()) == Some(name)
+    })
+}
+
+fn switch_world(
+    mut switch_events: EventReader<SwitchWorld>,
+    asset_server: Res<AssetServer>,
+    mut registry: ResMut<WorldRegistry>,
+    mut world: ResMut<GameWorld>,
+    mut pending_switch: ResMut<PendingSwitch>,
+) {
+    for event in switch_events.iter() {
+        info!("Switching to world '{}'", event.path);
+        let handle = registry.load(&asset_server, &event.path);
+        if world.project != handle {
+            world.project = handle;
+        }
+        registry.current = Some(event.path.clone());
+        pending_switch.0 = Some(event.clone());
+    }
+}
+
+fn place_player_at_entry_point(
+    world: Res<GameWorld>,
+    maps: Res<Assets<WorldProject>>,
+    mut pending_switch: ResMut<PendingSwitch>,
+    mut teleport_events: EventWriter<TeleportPlayer>,
+) {
+    let map = match (&pending_switch.0, maps.get(&world.project)) {
+        (Some(_), Some(map)) => map,
+        _ => return,
+    };
+
+    let switch = pending_switch.0.take().unwrap();
+    let entry_point = switch.entry_point.and_then(|name| {
+        let entry_point = find_entry_point(map, &name);
+        if entry_point.is_none() {
+            warn!(
+                "Could not find entry point '{}', using the start point",
+                name
+            );
+        }
+        entry_point
+    });
+    match entry_point.or_else(|| map.find_entity(START_POINT_IDENTIFIER)) {
+        Some((level, entity)) => teleport_events.send(TeleportPlayer {
+            position: arrival_position(level, entity),
+        }),
+        None => warn!(
+            "World '{}' has no start point to place the player at",
+            switch.path
+        ),
+    }
+}
+
+fn show_loading_screen(mut egui: ResMut<bevy_egui::EguiContext>) {
+    egui::CentralPanel::default()
+        .frame(egui::Frame::none())
+        .show(egui.ctx_mut(), |ui| {
+            ui.centered_and_justified(|ui| ui.heading("Loading..."));
+        });
+}